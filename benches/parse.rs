@@ -0,0 +1,88 @@
+//! Compares parse throughput of the CSV, JSON Lines and binary transaction formats.
+//!
+//! Run with `cargo bench --bench parse`. Each format parses the same generated
+//! transactions from memory, so only parsing is measured.
+
+use std::hint::black_box;
+use std::io::Cursor;
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use rust_decimal::Decimal;
+
+use rust_toy_tx_engine::{
+    binary::BinaryTransactionSource,
+    convert::write_transactions,
+    report::Format,
+    transaction::{
+        CsvTransactionSource, JsonLinesTransactionSource, Transaction, TransactionSource,
+        TransactionType,
+    },
+};
+
+const ROWS: u32 = 100_000;
+
+fn generate(rows: u32) -> Vec<Transaction> {
+    (1..=rows)
+        .map(|tx| {
+            let client = (tx % 1000) as u16;
+            match tx % 10 {
+                0 => Transaction {
+                    r#type: TransactionType::Dispute,
+                    client,
+                    tx: tx - 1,
+                    amount: None,
+                },
+                1..=3 => Transaction {
+                    r#type: TransactionType::Withdrawal,
+                    client,
+                    tx,
+                    amount: Some(Decimal::new(i64::from(tx % 5000), 4)),
+                },
+                _ => Transaction {
+                    r#type: TransactionType::Deposit,
+                    client,
+                    tx,
+                    amount: Some(Decimal::new(i64::from(tx % 100_000), 4)),
+                },
+            }
+        })
+        .collect()
+}
+
+fn encode(transactions: &[Transaction], format: Format) -> Vec<u8> {
+    let mut output = Vec::new();
+    write_transactions(transactions.iter().cloned(), format, &mut output).unwrap();
+    output
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let transactions = generate(ROWS);
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Elements(u64::from(ROWS)));
+
+    for format in [Format::Csv, Format::JsonLines, Format::Binary] {
+        let data = encode(&transactions, format);
+        group.bench_function(format!("{format:?}"), |b| {
+            b.iter(|| {
+                let reader = Cursor::new(data.clone());
+                let count = match format {
+                    Format::Csv => CsvTransactionSource::from_reader(reader)
+                        .transactions()
+                        .count(),
+                    Format::JsonLines => JsonLinesTransactionSource::from_reader(reader)
+                        .transactions()
+                        .count(),
+                    Format::Binary => BinaryTransactionSource::from_reader(reader)
+                        .transactions()
+                        .count(),
+                    Format::Ofx | Format::Camt053 => unreachable!("not benchmarked"),
+                };
+                black_box(count)
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_parse);
+criterion_main!(benches);
@@ -0,0 +1,96 @@
+//! Compares the single-threaded `Engine` with `ShardedEngine` on a generated CSV file.
+//!
+//! Run with `cargo bench --bench sharded`. The file holds 10M rows from the workload
+//! generator, or `SHARDED_BENCH_ROWS` rows, and is written once to the cargo target
+//! directory and reused by later runs. Each iteration reads and applies the whole file.
+
+use std::fs::{self, File};
+use std::hint::black_box;
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+
+use rust_toy_tx_engine::{
+    convert::write_transactions,
+    engine::Engine,
+    generate::{Workload, WorkloadConfig},
+    report::Format,
+    sharded::ShardedEngine,
+    transaction::CsvTransactionSource,
+};
+
+const DEFAULT_ROWS: u64 = 10_000_000;
+const CLIENTS: u16 = 10_000;
+
+fn rows() -> u64 {
+    std::env::var("SHARDED_BENCH_ROWS")
+        .ok()
+        .and_then(|rows| rows.parse().ok())
+        .unwrap_or(DEFAULT_ROWS)
+}
+
+// Write the workload to a CSV file unless an earlier run already did.
+fn input_file(rows: u64) -> PathBuf {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("sharded-{rows}.csv"));
+    if path.exists() {
+        return path;
+    }
+    let partial = path.with_extension("partial");
+    let workload = Workload::new(WorkloadConfig {
+        transactions: rows,
+        clients: CLIENTS,
+        ..WorkloadConfig::default()
+    });
+    let file = BufWriter::new(File::create(&partial).unwrap());
+    write_transactions(workload, Format::Csv, file).unwrap();
+    fs::rename(&partial, &path).unwrap();
+    path
+}
+
+fn report(engine: &Engine) -> Vec<u8> {
+    let mut output = Vec::new();
+    engine.write_report(&mut output).unwrap();
+    output
+}
+
+fn process_single(path: &str) -> Engine {
+    let mut engine = Engine::new();
+    engine.process_transactions(&mut CsvTransactionSource::new(path));
+    engine
+}
+
+fn process_sharded(path: &str, shards: usize) -> Engine {
+    let mut engine = ShardedEngine::new(shards);
+    engine.process_transactions(&mut CsvTransactionSource::new(path));
+    engine.finish()
+}
+
+fn bench_sharded(c: &mut Criterion) {
+    let rows = rows();
+    let path = input_file(rows);
+    let path = path.to_str().unwrap();
+
+    let expected = report(&process_single(path));
+    for shards in [2, 4, 8] {
+        assert_eq!(
+            report(&process_sharded(path, shards)),
+            expected,
+            "sharded report differs with {shards} shards"
+        );
+    }
+
+    let mut group = c.benchmark_group("process");
+    group.throughput(Throughput::Elements(rows));
+    group.sample_size(10);
+    group.bench_function("single", |b| b.iter(|| black_box(process_single(path))));
+    for shards in [2, 4, 8] {
+        group.bench_function(format!("sharded/{shards}"), |b| {
+            b.iter(|| black_box(process_sharded(path, shards)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_sharded);
+criterion_main!(benches);
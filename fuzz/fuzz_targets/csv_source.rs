@@ -0,0 +1,20 @@
+//! Arbitrary bytes through the CSV source, and whatever parses through the engine.
+
+#![no_main]
+
+use std::io::Cursor;
+
+use libfuzzer_sys::fuzz_target;
+use rust_toy_tx_engine::{
+    engine::Engine,
+    transaction::{CsvTransactionSource, TransactionSource},
+};
+use rust_toy_tx_engine_fuzz::apply_checked;
+
+fuzz_target!(|data: &[u8]| {
+    let mut source = CsvTransactionSource::from_reader(Cursor::new(data.to_vec()));
+    let mut engine = Engine::new();
+    for transaction in source.transactions() {
+        apply_checked(&mut engine, transaction);
+    }
+});
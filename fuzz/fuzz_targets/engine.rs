@@ -0,0 +1,53 @@
+//! Structured transaction sequences through the engine.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use rust_decimal::Decimal;
+use rust_toy_tx_engine::{
+    engine::Engine,
+    transaction::{Transaction, TransactionType},
+};
+use rust_toy_tx_engine_fuzz::apply_checked;
+
+#[derive(Debug, Arbitrary)]
+enum Type {
+    Deposit,
+    Withdrawal,
+    Dispute,
+    Resolve,
+    Chargeback,
+}
+
+/// Clients and tx ids are small so that sequences often refer to each other.
+#[derive(Debug, Arbitrary)]
+struct Input {
+    r#type: Type,
+    client: u8,
+    tx: u8,
+    // Mantissa and scale, any scale above 28 is wrapped.
+    amount: Option<(i64, u8)>,
+}
+
+fuzz_target!(|inputs: Vec<Input>| {
+    let mut engine = Engine::new();
+    for input in inputs {
+        let r#type = match input.r#type {
+            Type::Deposit => TransactionType::Deposit,
+            Type::Withdrawal => TransactionType::Withdrawal,
+            Type::Dispute => TransactionType::Dispute,
+            Type::Resolve => TransactionType::Resolve,
+            Type::Chargeback => TransactionType::Chargeback,
+        };
+        let transaction = Transaction {
+            r#type,
+            client: input.client.into(),
+            tx: input.tx.into(),
+            amount: input
+                .amount
+                .map(|(mantissa, scale)| Decimal::new(mantissa, u32::from(scale % 29))),
+        };
+        apply_checked(&mut engine, transaction);
+    }
+});
@@ -0,0 +1,75 @@
+//! Invariants checked by the fuzz targets after every transaction.
+
+use std::collections::HashMap;
+
+use rust_decimal::Decimal;
+use rust_toy_tx_engine::{
+    engine::Engine,
+    ledger::LedgerAccount,
+    transaction::{DisputeState, Transaction, TransactionType},
+};
+
+/// Apply `transaction` and panic if it breaks a rule of the engine: tx ids of deposits
+/// and withdrawals are applied once, and locked accounts take no deposits or
+/// withdrawals.
+pub fn apply_checked(engine: &mut Engine, transaction: Transaction) {
+    let Transaction {
+        r#type, client, tx, ..
+    } = transaction;
+    let transfer = matches!(
+        r#type,
+        TransactionType::Deposit | TransactionType::Withdrawal
+    );
+    let duplicate = transfer && engine.transactions.contains_key(&tx);
+    let locked_total = engine
+        .accounts
+        .get(&client)
+        .filter(|account| account.is_locked)
+        .map(|account| account.total());
+
+    let result = engine.apply_transaction(transaction);
+
+    if duplicate {
+        assert!(result.is_err(), "tx {tx} was applied twice");
+    }
+    if let Some(total) = locked_total
+        && transfer
+    {
+        assert!(result.is_err(), "locked account {client} took tx {tx}");
+        assert_eq!(engine.accounts[&client].total(), total);
+    }
+    check_balances(engine);
+}
+
+/// Panic unless every account adds up: the total is available plus held funds, the
+/// ledger agrees with the account, and held funds are exactly those of open disputes.
+pub fn check_balances(engine: &Engine) {
+    let mut disputed: HashMap<u16, Decimal> = HashMap::new();
+    for record in engine.transactions.values() {
+        if record.dispute_state == DisputeState::Disputed {
+            *disputed.entry(record.transaction.client).or_default() +=
+                record.transaction.amount.unwrap_or_default();
+        }
+    }
+
+    for summary in engine.account_summaries() {
+        let client = summary.client;
+        assert_eq!(summary.total, summary.available + summary.held);
+        assert_eq!(
+            engine
+                .ledger
+                .balance(LedgerAccount::ClientAvailable(client)),
+            summary.available
+        );
+        assert_eq!(
+            engine.ledger.balance(LedgerAccount::ClientHeld(client)),
+            summary.held
+        );
+        assert_eq!(
+            summary.held,
+            disputed.get(&client).copied().unwrap_or_default()
+        );
+        assert!(summary.held >= Decimal::ZERO);
+    }
+    assert_eq!(engine.ledger.trial_balance().total(), Decimal::ZERO);
+}
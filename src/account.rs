@@ -1,16 +1,22 @@
 use log::{info, warn};
 use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use crate::ledger::{Ledger, LedgerAccount};
+use crate::transaction::TransactionType;
+
 /// Account represents a user's account with total, held, and calculated available balances.
+/// The balances are a view of the client's ledger accounts and are refreshed from the
+/// `Ledger` after every posting; operations never change them directly.
 #[derive(Debug, Clone)]
 pub struct Account {
     // Unique identifier for the client
     pub client_id: u16,
-    // Total funds (available + held)
-    pub total: Decimal,
-    // Held funds (e.g., in dispute)
-    pub held: Decimal,
+    // Total funds (available + held), refreshed from the ledger
+    total: Decimal,
+    // Held funds (e.g., in dispute), refreshed from the ledger
+    held: Decimal,
     // Indicates if the account is locked
     pub is_locked: bool,
 }
@@ -25,13 +31,28 @@ impl Account {
         }
     }
 
+    /// Total funds, available plus held.
+    pub fn total(&self) -> Decimal {
+        self.total
+    }
+
+    /// Funds held by open disputes.
+    pub fn held(&self) -> Decimal {
+        self.held
+    }
+
     /// Calculate available funds as total - held.
     pub fn get_available(&self) -> Decimal {
         self.total - self.held
     }
 
     // Deposit funds into the account. If account is locked, the deposit should not be processed.
-    pub fn deposit(&mut self, tx: u32, amount: Decimal) -> Result<(), AccountError> {
+    pub fn deposit(
+        &mut self,
+        ledger: &mut Ledger,
+        tx: u32,
+        amount: Decimal,
+    ) -> Result<(), AccountError> {
         if self.is_locked {
             warn!(
                 "Account {} is locked. Deposit of {} for tx {} not processed.",
@@ -39,7 +60,14 @@ impl Account {
             );
             return Err(AccountError::AccountLocked(self.client_id));
         }
-        self.total += amount;
+        self.post(
+            ledger,
+            tx,
+            TransactionType::Deposit,
+            LedgerAccount::SettlementClearing,
+            self.available_account(),
+            amount,
+        );
         info!(
             "Deposit of {} for client {} (tx {}) processed.",
             amount, self.client_id, tx
@@ -48,7 +76,12 @@ impl Account {
     }
 
     /// Withdraw funds from the account. If account is locked, the withdrawal should not be processed.
-    pub fn withdraw(&mut self, tx: u32, amount: Decimal) -> Result<(), AccountError> {
+    pub fn withdraw(
+        &mut self,
+        ledger: &mut Ledger,
+        tx: u32,
+        amount: Decimal,
+    ) -> Result<(), AccountError> {
         if self.is_locked {
             warn!(
                 "Account {} is locked. Withdrawal of {} for tx {} not processed.",
@@ -67,7 +100,14 @@ impl Account {
             );
             return Err(AccountError::InsufficientFunds(self.client_id));
         }
-        self.total -= amount;
+        self.post(
+            ledger,
+            tx,
+            TransactionType::Withdrawal,
+            self.available_account(),
+            LedgerAccount::SettlementClearing,
+            amount,
+        );
         info!(
             "Withdrawal of {} for client {} (tx {}) processed.",
             amount, self.client_id, tx
@@ -75,26 +115,111 @@ impl Account {
         Ok(())
     }
 
-    /// Dispute a transaction by increasing held funds for the account.
-    pub fn dispute(&mut self, amount: Decimal) -> Result<(), AccountError> {
-        self.held += amount;
+    /// Dispute a transaction by moving its amount from available to held funds.
+    pub fn dispute(
+        &mut self,
+        ledger: &mut Ledger,
+        tx: u32,
+        amount: Decimal,
+    ) -> Result<(), AccountError> {
+        self.post(
+            ledger,
+            tx,
+            TransactionType::Dispute,
+            self.available_account(),
+            self.held_account(),
+            amount,
+        );
         Ok(())
     }
 
     /// Resolve a dispute by releasing the held funds.
-    pub fn resolve(&mut self, amount: Decimal) -> Result<(), AccountError> {
-        self.held -= amount;
+    pub fn resolve(
+        &mut self,
+        ledger: &mut Ledger,
+        tx: u32,
+        amount: Decimal,
+    ) -> Result<(), AccountError> {
+        self.post(
+            ledger,
+            tx,
+            TransactionType::Resolve,
+            self.held_account(),
+            self.available_account(),
+            amount,
+        );
         Ok(())
     }
 
-    /// Chargeback a transaction by withdrawing held funds for the account.
+    /// Chargeback a transaction by writing the held funds off as a chargeback loss.
     /// Total should be reduced by amount and account should be locked.
-    pub fn chargeback(&mut self, amount: Decimal) -> Result<(), AccountError> {
-        self.held -= amount;
-        self.total -= amount;
+    pub fn chargeback(
+        &mut self,
+        ledger: &mut Ledger,
+        tx: u32,
+        amount: Decimal,
+    ) -> Result<(), AccountError> {
+        self.post(
+            ledger,
+            tx,
+            TransactionType::Chargeback,
+            self.held_account(),
+            LedgerAccount::ChargebackLoss,
+            amount,
+        );
         self.is_locked = true;
         Ok(())
     }
+
+    fn available_account(&self) -> LedgerAccount {
+        LedgerAccount::ClientAvailable(self.client_id)
+    }
+
+    fn held_account(&self) -> LedgerAccount {
+        LedgerAccount::ClientHeld(self.client_id)
+    }
+
+    // Post the entry and refresh the balances from the ledger.
+    fn post(
+        &mut self,
+        ledger: &mut Ledger,
+        tx: u32,
+        r#type: TransactionType,
+        from: LedgerAccount,
+        to: LedgerAccount,
+        amount: Decimal,
+    ) {
+        ledger.post(tx, r#type, from, to, amount);
+        self.refresh(ledger);
+    }
+
+    /// Refresh the balances from the client's ledger accounts.
+    pub(crate) fn refresh(&mut self, ledger: &Ledger) {
+        self.held = ledger.balance(self.held_account());
+        self.total = ledger.balance(self.available_account()) + self.held;
+    }
+}
+
+/// AccountSummary is the reported view of an account, one row of the account report.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountSummary {
+    pub client: u16,
+    pub available: Decimal,
+    pub held: Decimal,
+    pub total: Decimal,
+    pub locked: bool,
+}
+
+impl From<&Account> for AccountSummary {
+    fn from(account: &Account) -> Self {
+        Self {
+            client: account.client_id,
+            available: account.get_available(),
+            held: account.held(),
+            total: account.total(),
+            locked: account.is_locked,
+        }
+    }
 }
 
 impl Default for Account {
@@ -118,93 +243,104 @@ mod tests {
 
     #[test]
     fn test_deposit() {
+        let mut ledger = Ledger::new();
         let mut account = Account::new(1);
         let deposit_amount = Decimal::new(100, 2);
 
-        assert!(account.deposit(1, deposit_amount).is_ok());
+        assert!(account.deposit(&mut ledger, 1, deposit_amount).is_ok());
         assert_eq!(account.get_available(), deposit_amount);
-        assert_eq!(account.total, deposit_amount);
+        assert_eq!(account.total(), deposit_amount);
     }
 
     #[test]
     fn test_withdrawal() {
+        let mut ledger = Ledger::new();
         let mut account = Account::new(1);
         let deposit_amount = Decimal::new(100, 2);
         let withdrawal_amount = Decimal::new(50, 2);
 
-        account.deposit(1, deposit_amount).unwrap();
-        assert!(account.withdraw(2, withdrawal_amount).is_ok());
+        account.deposit(&mut ledger, 1, deposit_amount).unwrap();
+        assert!(account.withdraw(&mut ledger, 2, withdrawal_amount).is_ok());
         assert_eq!(account.get_available(), deposit_amount - withdrawal_amount);
-        assert_eq!(account.total, deposit_amount - withdrawal_amount);
+        assert_eq!(account.total(), deposit_amount - withdrawal_amount);
     }
 
     #[test]
     fn test_insufficient_funds() {
+        let mut ledger = Ledger::new();
         let mut account = Account::new(1);
         let deposit_amount = Decimal::new(100, 2);
         let withdrawal_amount = Decimal::new(50, 2);
 
-        account.deposit(1, deposit_amount).unwrap();
-        let result = account.withdraw(3, deposit_amount + withdrawal_amount);
+        account.deposit(&mut ledger, 1, deposit_amount).unwrap();
+        let result = account.withdraw(&mut ledger, 3, deposit_amount + withdrawal_amount);
         assert!(result.is_err());
         assert_eq!(account.get_available(), deposit_amount);
-        assert_eq!(account.total, deposit_amount);
+        assert_eq!(account.total(), deposit_amount);
     }
 
     #[test]
     fn test_dispute() {
+        let mut ledger = Ledger::new();
         let mut account = Account::new(1);
         let deposit_amount = Decimal::new(100, 2);
         let dispute_amount1 = Decimal::new(30, 2);
         let dispute_amount2 = Decimal::new(80, 2);
 
-        account.deposit(1, deposit_amount).unwrap();
+        account.deposit(&mut ledger, 1, deposit_amount).unwrap();
 
-        let result = account.dispute(dispute_amount1);
+        let result = account.dispute(&mut ledger, 1, dispute_amount1);
         assert!(result.is_ok());
-        assert_eq!(account.held, dispute_amount1);
+        assert_eq!(account.held(), dispute_amount1);
         assert_eq!(account.get_available(), deposit_amount - dispute_amount1);
 
-        let result = account.dispute(dispute_amount2);
+        let result = account.dispute(&mut ledger, 1, dispute_amount2);
         assert!(result.is_ok());
-        assert_eq!(account.held, dispute_amount1 + dispute_amount2);
+        assert_eq!(account.held(), dispute_amount1 + dispute_amount2);
         assert_eq!(
             account.get_available(),
             deposit_amount - dispute_amount1 - dispute_amount2
         );
-        assert_eq!(account.total, deposit_amount);
+        assert_eq!(account.total(), deposit_amount);
         assert_eq!(account.get_available(), Decimal::new(-10, 2));
-        assert_eq!(account.is_locked, false);
+        assert!(!account.is_locked);
     }
 
     #[test]
     fn test_resolve() {
+        let mut ledger = Ledger::new();
         let mut account = Account::new(1);
         let deposit_amount = Decimal::new(100, 2);
         let dispute_amount = Decimal::new(30, 2);
 
-        account.deposit(1, deposit_amount).unwrap();
-        account.dispute(dispute_amount).unwrap();
+        account.deposit(&mut ledger, 1, deposit_amount).unwrap();
+        account.dispute(&mut ledger, 1, dispute_amount).unwrap();
 
-        let result = account.resolve(dispute_amount);
+        let result = account.resolve(&mut ledger, 1, dispute_amount);
         assert!(result.is_ok());
-        assert_eq!(account.held, Decimal::ZERO);
+        assert_eq!(account.held(), Decimal::ZERO);
         assert_eq!(account.get_available(), deposit_amount);
     }
 
     #[test]
     fn test_chargeback() {
+        let mut ledger = Ledger::new();
         let mut account = Account::new(1);
         let deposit_amount = Decimal::new(100, 2);
         let dispute_amount = Decimal::new(50, 2);
 
-        account.total = deposit_amount;
-        account.held = dispute_amount;
+        account.deposit(&mut ledger, 1, deposit_amount).unwrap();
+        account.dispute(&mut ledger, 1, dispute_amount).unwrap();
 
-        assert!(account.chargeback(dispute_amount).is_ok());
+        assert!(account.chargeback(&mut ledger, 1, dispute_amount).is_ok());
 
-        assert_eq!(account.total, deposit_amount - dispute_amount);
-        assert_eq!(account.held, Decimal::ZERO);
+        assert_eq!(account.total(), deposit_amount - dispute_amount);
+        assert_eq!(account.held(), Decimal::ZERO);
         assert!(account.is_locked);
+        assert_eq!(
+            ledger.balance(LedgerAccount::ChargebackLoss),
+            dispute_amount
+        );
+        assert_eq!(ledger.trial_balance().total(), Decimal::ZERO);
     }
 }
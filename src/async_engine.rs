@@ -0,0 +1,261 @@
+use futures::stream::{self, BoxStream, StreamExt};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::sync::mpsc;
+
+use crate::engine::Engine;
+use crate::transaction::{LineParser, MalformedRows, Transaction};
+
+/// Default number of transactions buffered between the sources and the engine.
+pub const DEFAULT_CHANNEL_CAPACITY: usize = 1024;
+
+/// AsyncTransactionSource is the async counterpart of `TransactionSource`.
+/// It provides transactions as a `Stream`, e.g. from sockets, files or channels.
+pub trait AsyncTransactionSource: Send {
+    fn transactions(&mut self) -> BoxStream<'static, Transaction>;
+
+    /// The rows skipped so far because they could not be parsed. Clones share the
+    /// count, so a handle taken before the source is consumed sees every skipped row.
+    fn malformed(&self) -> MalformedRows {
+        MalformedRows::default()
+    }
+}
+
+/// ChannelTransactionSource yields transactions received on a tokio channel.
+pub struct ChannelTransactionSource {
+    receiver: Option<mpsc::Receiver<Transaction>>,
+}
+
+impl ChannelTransactionSource {
+    pub fn new(receiver: mpsc::Receiver<Transaction>) -> Self {
+        Self {
+            receiver: Some(receiver),
+        }
+    }
+}
+
+impl AsyncTransactionSource for ChannelTransactionSource {
+    fn transactions(&mut self) -> BoxStream<'static, Transaction> {
+        match self.receiver.take() {
+            Some(receiver) => stream::unfold(receiver, |mut receiver| async move {
+                receiver.recv().await.map(|tx| (tx, receiver))
+            })
+            .boxed(),
+            None => stream::empty().boxed(),
+        }
+    }
+}
+
+/// AsyncCsvTransactionSource reads CSV rows (header first) from any `AsyncRead`.
+/// Rows that fail to parse are skipped and counted in `malformed`.
+pub struct AsyncCsvTransactionSource<R> {
+    reader: Option<R>,
+    malformed: MalformedRows,
+}
+
+impl<R: AsyncRead + Send + Unpin + 'static> AsyncCsvTransactionSource<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader: Some(reader),
+            malformed: MalformedRows::default(),
+        }
+    }
+}
+
+impl<R: AsyncRead + Send + Unpin + 'static> AsyncTransactionSource
+    for AsyncCsvTransactionSource<R>
+{
+    fn transactions(&mut self) -> BoxStream<'static, Transaction> {
+        let Some(reader) = self.reader.take() else {
+            return stream::empty().boxed();
+        };
+
+        let lines = BufReader::new(reader).lines();
+        let malformed = self.malformed.clone();
+        stream::unfold(
+            (lines, None, 0u64, malformed),
+            |(mut lines, mut parser, mut line_number, malformed)| async move {
+                loop {
+                    line_number += 1;
+                    let line = match lines.next_line().await {
+                        Ok(Some(line)) => line,
+                        Ok(None) => return None,
+                        Err(e) => {
+                            // The reader cannot recover from I/O errors, stop instead of
+                            // retrying.
+                            malformed.skip(format_args!("line {line_number}"), e);
+                            return None;
+                        }
+                    };
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let Some(row_parser) = &mut parser else {
+                        match LineParser::new(&line) {
+                            Ok(header_parser) => parser = Some(header_parser),
+                            Err(e) => {
+                                // Without the required columns no row can be parsed.
+                                malformed.skip("header", e);
+                                return None;
+                            }
+                        }
+                        continue;
+                    };
+                    match row_parser.parse(&line) {
+                        Ok(tx) => return Some((tx, (lines, parser, line_number, malformed))),
+                        Err(e) => malformed.skip(format_args!("line {line_number}"), e),
+                    }
+                }
+            },
+        )
+        .boxed()
+    }
+
+    fn malformed(&self) -> MalformedRows {
+        self.malformed.clone()
+    }
+}
+
+/// AsyncEngineDriver feeds an `Engine` from many async sources concurrently.
+///
+/// Each source is polled by its own tokio task and forwards into a bounded channel
+/// that a single consumer applies to the engine. Transactions from one source keep
+/// their order; transactions from different sources are interleaved as they arrive.
+/// When the engine falls behind the channel fills up and the source tasks wait,
+/// so sources are only read as fast as the engine can apply them. Take each source's
+/// `malformed` handle before adding it to count the rows it skipped.
+pub struct AsyncEngineDriver {
+    engine: Engine,
+    capacity: usize,
+    sources: Vec<BoxStream<'static, Transaction>>,
+}
+
+impl AsyncEngineDriver {
+    pub fn new(engine: Engine) -> Self {
+        Self {
+            engine,
+            capacity: DEFAULT_CHANNEL_CAPACITY,
+            sources: Vec::new(),
+        }
+    }
+
+    /// Set how many transactions may be buffered before sources are paused.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    pub fn add_source<S: AsyncTransactionSource>(&mut self, mut source: S) {
+        self.sources.push(source.transactions());
+    }
+
+    /// Consume all sources to completion and return the engine.
+    pub async fn run(mut self) -> Engine {
+        let (sender, mut receiver) = mpsc::channel(self.capacity);
+
+        for mut transactions in self.sources.drain(..) {
+            let sender = sender.clone();
+            tokio::spawn(async move {
+                while let Some(tx) = transactions.next().await {
+                    if sender.send(tx).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        // Only the source tasks hold senders now, so the loop ends once they all finish.
+        drop(sender);
+
+        while let Some(tx) = receiver.recv().await {
+            // Rejections are logged by the engine.
+            let _ = self.engine.apply_transaction(tx);
+        }
+        self.engine
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::TransactionType;
+    use rust_decimal::Decimal;
+
+    fn deposit(client: u16, tx: u32, amount: i64) -> Transaction {
+        Transaction {
+            r#type: TransactionType::Deposit,
+            client,
+            tx,
+            amount: Some(Decimal::new(amount, 2)),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_driver_consumes_multiple_sources() {
+        let csv = "type, client, tx, amount\n\
+                   deposit, 1, 1, 5.0\n\
+                   not a row\n\
+                   withdrawal, 1, 2, 2.0\n\
+                   dispute, 1, 1,\n";
+        let (sender, receiver) = mpsc::channel(1);
+
+        let mut driver = AsyncEngineDriver::new(Engine::new()).with_capacity(1);
+        let source = AsyncCsvTransactionSource::new(csv.as_bytes());
+        let malformed = source.malformed();
+        driver.add_source(source);
+        driver.add_source(ChannelTransactionSource::new(receiver));
+
+        let producer = tokio::spawn(async move {
+            for tx in 10..20 {
+                sender.send(deposit(2, tx, 100)).await.unwrap();
+            }
+        });
+
+        let engine = driver.run().await;
+        producer.await.unwrap();
+
+        let account1 = engine.accounts.get(&1).unwrap();
+        assert_eq!(account1.total(), Decimal::new(30, 1));
+        assert_eq!(account1.held(), Decimal::new(50, 1));
+
+        let account2 = engine.accounts.get(&2).unwrap();
+        assert_eq!(account2.total(), Decimal::new(1000, 2));
+
+        assert_eq!(malformed.count(), 1);
+        assert!(malformed.errors()[0].starts_with("line 3: "));
+    }
+
+    #[tokio::test]
+    async fn test_csv_source_counts_unreadable_input() {
+        let mut source = AsyncCsvTransactionSource::new(&b"type,client,tx,amount\n\xff\n"[..]);
+        assert_eq!(source.transactions().count().await, 0);
+        assert_eq!(source.malformed().count(), 1);
+        assert!(source.malformed().errors()[0].starts_with("line 2: "));
+
+        let mut source = AsyncCsvTransactionSource::new(&b"type,client\ndeposit,1\n"[..]);
+        assert_eq!(source.transactions().count().await, 0);
+        assert_eq!(source.malformed().count(), 1);
+        assert!(source.malformed().errors()[0].starts_with("header: "));
+    }
+
+    #[tokio::test]
+    async fn test_driver_preserves_order_within_source() {
+        // Each withdrawal would fail if it overtook its deposit.
+        let (sender, receiver) = mpsc::channel(4);
+        let mut driver = AsyncEngineDriver::new(Engine::new()).with_capacity(2);
+        driver.add_source(ChannelTransactionSource::new(receiver));
+
+        let producer = tokio::spawn(async move {
+            for tx in (0..200).step_by(2) {
+                sender.send(deposit(1, tx, 100)).await.unwrap();
+                let mut withdrawal = deposit(1, tx + 1, 100);
+                withdrawal.r#type = TransactionType::Withdrawal;
+                sender.send(withdrawal).await.unwrap();
+            }
+        });
+
+        let engine = driver.run().await;
+        producer.await.unwrap();
+
+        assert_eq!(engine.accounts.get(&1).unwrap().total(), Decimal::ZERO);
+        assert_eq!(engine.transactions.len(), 200);
+    }
+}
@@ -0,0 +1,208 @@
+//! Structured audit events describing every decision of the engine.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use log::warn;
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::history::Balances;
+use crate::transaction::TransactionType;
+
+/// AuditEvent is one decision taken by the engine. Balances are those of the client's
+/// account, `None` while the client has no account.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AuditEvent {
+    /// A deposit or withdrawal was applied.
+    TransactionApplied {
+        r#type: TransactionType,
+        client: u16,
+        tx: u32,
+        amount: Decimal,
+        before: Option<Balances>,
+        after: Balances,
+    },
+    /// A transaction was rejected and left the account unchanged.
+    TransactionRejected {
+        r#type: TransactionType,
+        client: u16,
+        tx: u32,
+        reason: String,
+        balances: Option<Balances>,
+    },
+    /// The funds of transaction `tx` are held by a dispute.
+    DisputeOpened {
+        client: u16,
+        tx: u32,
+        amount: Decimal,
+        before: Balances,
+        after: Balances,
+    },
+    /// The dispute of transaction `tx` was resolved and its funds released.
+    DisputeResolved {
+        client: u16,
+        tx: u32,
+        amount: Decimal,
+        before: Balances,
+        after: Balances,
+    },
+    /// The disputed funds of transaction `tx` were charged back.
+    ChargebackApplied {
+        client: u16,
+        tx: u32,
+        amount: Decimal,
+        before: Balances,
+        after: Balances,
+    },
+    /// The account was locked by the chargeback of transaction `tx`.
+    AccountLocked {
+        client: u16,
+        tx: u32,
+        before: Balances,
+        after: Balances,
+    },
+}
+
+/// AuditObserver receives the audit events of an engine, in the order they happen.
+pub trait AuditObserver: Send {
+    fn on_event(&mut self, event: &AuditEvent);
+}
+
+impl<F: FnMut(&AuditEvent) + Send> AuditObserver for F {
+    fn on_event(&mut self, event: &AuditEvent) {
+        self(event)
+    }
+}
+
+/// JsonLinesAuditSink writes every event as one JSON object per line.
+pub struct JsonLinesAuditSink<W: Write + Send> {
+    writer: W,
+}
+
+impl JsonLinesAuditSink<BufWriter<File>> {
+    /// Append events to the audit file at `path`, creating it if needed.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::options().create(true).append(true).open(path)?;
+        Ok(Self::new(BufWriter::new(file)))
+    }
+}
+
+impl<W: Write + Send> JsonLinesAuditSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write + Send> AuditObserver for JsonLinesAuditSink<W> {
+    fn on_event(&mut self, event: &AuditEvent) {
+        let result = serde_json::to_writer(&mut self.writer, event)
+            .map_err(io::Error::from)
+            .and_then(|()| self.writer.write_all(b"\n"));
+        if let Err(e) = result {
+            warn!("Failed to write audit event: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Engine;
+    use crate::test_support::tx;
+    use std::sync::{Arc, Mutex};
+
+    fn balances(available: i64, held: i64, locked: bool) -> Balances {
+        Balances {
+            available: Decimal::new(available, 2),
+            held: Decimal::new(held, 2),
+            total: Decimal::new(available + held, 2),
+            locked,
+        }
+    }
+
+    #[test]
+    fn test_engine_emits_events() {
+        use TransactionType::*;
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&events);
+        let mut engine = Engine::new().with_observer(move |event: &AuditEvent| {
+            recorded.lock().unwrap().push(event.clone());
+        });
+        for transaction in [
+            tx(Deposit, 1, 1, Some(500)),
+            tx(Withdrawal, 1, 2, Some(900)),
+            tx(Dispute, 1, 1, None),
+            tx(Chargeback, 1, 1, None),
+        ] {
+            let _ = engine.apply_transaction(transaction);
+        }
+
+        let amount = Decimal::new(500, 2);
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                AuditEvent::TransactionApplied {
+                    r#type: Deposit,
+                    client: 1,
+                    tx: 1,
+                    amount,
+                    before: None,
+                    after: balances(500, 0, false),
+                },
+                AuditEvent::TransactionRejected {
+                    r#type: Withdrawal,
+                    client: 1,
+                    tx: 2,
+                    reason: "Insufficient funds for client 1.".to_string(),
+                    balances: Some(balances(500, 0, false)),
+                },
+                AuditEvent::DisputeOpened {
+                    client: 1,
+                    tx: 1,
+                    amount,
+                    before: balances(500, 0, false),
+                    after: balances(0, 500, false),
+                },
+                AuditEvent::ChargebackApplied {
+                    client: 1,
+                    tx: 1,
+                    amount,
+                    before: balances(0, 500, false),
+                    after: balances(0, 0, true),
+                },
+                AuditEvent::AccountLocked {
+                    client: 1,
+                    tx: 1,
+                    before: balances(0, 500, false),
+                    after: balances(0, 0, true),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_jsonl_sink() {
+        let mut sink = JsonLinesAuditSink::new(Vec::new());
+        sink.on_event(&AuditEvent::DisputeResolved {
+            client: 2,
+            tx: 7,
+            amount: Decimal::new(15, 1),
+            before: balances(0, 150, false),
+            after: balances(150, 0, false),
+        });
+
+        assert_eq!(
+            String::from_utf8(sink.into_inner()).unwrap(),
+            "{\"event\":\"dispute_resolved\",\"client\":2,\"tx\":7,\"amount\":\"1.5\",\
+             \"before\":{\"available\":\"0.00\",\"held\":\"1.50\",\"total\":\"1.50\",\"locked\":false},\
+             \"after\":{\"available\":\"1.50\",\"held\":\"0.00\",\"total\":\"1.50\",\"locked\":false}}\n"
+        );
+    }
+}
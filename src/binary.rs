@@ -0,0 +1,305 @@
+//! Compact fixed-size binary encoding of transactions.
+//!
+//! A file starts with the 4 byte magic `TXB1`, followed by one 15 byte record per
+//! transaction, all integers little-endian:
+//!
+//! | offset | size | field                                             |
+//! |--------|------|---------------------------------------------------|
+//! | 0      | 1    | type: 0 deposit, 1 withdrawal, 2 dispute, 3 resolve, 4 chargeback |
+//! | 1      | 2    | client (u16)                                      |
+//! | 3      | 4    | tx (u32)                                          |
+//! | 7      | 8    | amount (i64) scaled by 10^4 (`engine::AMOUNT_SCALE`), `i64::MIN` if absent |
+
+use std::io::{self, BufRead, Read, Write};
+
+use rust_decimal::Decimal;
+use thiserror::Error;
+
+use crate::engine::AMOUNT_SCALE;
+use crate::input::Input;
+use crate::transaction::{MalformedRows, Transaction, TransactionSource, TransactionType};
+
+/// Magic bytes at the start of every binary transaction file.
+pub const MAGIC: &[u8; 4] = b"TXB1";
+
+/// Size of one encoded transaction in bytes.
+pub const RECORD_SIZE: usize = 15;
+
+// Stored in place of the amount of disputes, resolves and chargebacks.
+const NO_AMOUNT: i64 = i64::MIN;
+
+/// BinaryError represents a transaction that cannot be encoded or decoded.
+#[derive(Debug, Error)]
+pub enum BinaryError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("Not a binary transaction file.")]
+    BadMagic,
+    #[error("Unknown transaction type byte {0}.")]
+    UnknownType(u8),
+    #[error("Input ends in the middle of a record.")]
+    TruncatedRecord,
+    #[error("Amount {0} does not fit in {AMOUNT_SCALE} decimal places.")]
+    AmountOutOfRange(Decimal),
+}
+
+/// Encode `transaction` as one fixed-size record.
+pub fn encode(transaction: &Transaction) -> Result<[u8; RECORD_SIZE], BinaryError> {
+    let amount = match transaction.amount {
+        None => NO_AMOUNT,
+        Some(amount) => scale_amount(amount)?,
+    };
+
+    let mut record = [0u8; RECORD_SIZE];
+    record[0] = type_byte(transaction.r#type);
+    record[1..3].copy_from_slice(&transaction.client.to_le_bytes());
+    record[3..7].copy_from_slice(&transaction.tx.to_le_bytes());
+    record[7..15].copy_from_slice(&amount.to_le_bytes());
+    Ok(record)
+}
+
+/// Decode one record written by `encode`.
+pub fn decode(record: &[u8; RECORD_SIZE]) -> Result<Transaction, BinaryError> {
+    let r#type = match record[0] {
+        0 => TransactionType::Deposit,
+        1 => TransactionType::Withdrawal,
+        2 => TransactionType::Dispute,
+        3 => TransactionType::Resolve,
+        4 => TransactionType::Chargeback,
+        other => return Err(BinaryError::UnknownType(other)),
+    };
+    let client = u16::from_le_bytes([record[1], record[2]]);
+    let tx = u32::from_le_bytes(record[3..7].try_into().expect("slice of 4 bytes"));
+    let amount = i64::from_le_bytes(record[7..15].try_into().expect("slice of 8 bytes"));
+
+    Ok(Transaction {
+        r#type,
+        client,
+        tx,
+        amount: (amount != NO_AMOUNT).then(|| Decimal::new(amount, AMOUNT_SCALE)),
+    })
+}
+
+fn type_byte(r#type: TransactionType) -> u8 {
+    match r#type {
+        TransactionType::Deposit => 0,
+        TransactionType::Withdrawal => 1,
+        TransactionType::Dispute => 2,
+        TransactionType::Resolve => 3,
+        TransactionType::Chargeback => 4,
+    }
+}
+
+// Amounts with more than AMOUNT_SCALE decimal places are rejected rather than rounded.
+fn scale_amount(amount: Decimal) -> Result<i64, BinaryError> {
+    let mut scaled = amount;
+    scaled.rescale(AMOUNT_SCALE);
+    if scaled != amount {
+        return Err(BinaryError::AmountOutOfRange(amount));
+    }
+    i64::try_from(scaled.mantissa())
+        .ok()
+        .filter(|&value| value != NO_AMOUNT)
+        .ok_or(BinaryError::AmountOutOfRange(amount))
+}
+
+/// BinaryTransactionWriter writes transactions in the binary format.
+pub struct BinaryTransactionWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> BinaryTransactionWriter<W> {
+    /// Create a writer, writing the file magic immediately.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(MAGIC)?;
+        Ok(Self { writer })
+    }
+
+    pub fn write(&mut self, transaction: &Transaction) -> Result<(), BinaryError> {
+        self.writer.write_all(&encode(transaction)?)?;
+        Ok(())
+    }
+
+    /// Flush and return the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// BinaryTransactionSource reads transactions in the binary format from a file,
+/// standard input (`-`) or any reader. Compressed input is decompressed while streaming.
+pub struct BinaryTransactionSource {
+    input: Option<Input>,
+    malformed: MalformedRows,
+}
+
+impl BinaryTransactionSource {
+    pub fn new(path: &str) -> Self {
+        Self {
+            input: Some(Input::Path(path.to_string())),
+            malformed: MalformedRows::default(),
+        }
+    }
+
+    pub fn from_reader<R: Read + 'static>(reader: R) -> Self {
+        Self {
+            input: Some(Input::Reader(Box::new(reader))),
+            malformed: MalformedRows::default(),
+        }
+    }
+}
+
+impl TransactionSource for BinaryTransactionSource {
+    fn transactions(&mut self) -> Box<dyn Iterator<Item = Transaction>> {
+        let Some(input) = self.input.take() else {
+            return Box::new(std::iter::empty());
+        };
+        let malformed = self.malformed.clone();
+        let reader = match input.open() {
+            Ok(reader) => reader,
+            Err(e) => {
+                malformed.skip("input", e);
+                return Box::new(std::iter::empty());
+            }
+        };
+        let mut records = match read_binary_transactions(reader) {
+            Ok(records) => records,
+            Err(e) => {
+                malformed.skip("header", e);
+                return Box::new(std::iter::empty());
+            }
+        };
+        let mut index = 0;
+        Box::new(std::iter::from_fn(move || {
+            loop {
+                index += 1;
+                match records.next()? {
+                    Ok(transaction) => return Some(transaction),
+                    // Records have a fixed size, so reading can carry on after a bad one.
+                    Err(e @ BinaryError::UnknownType(_)) => {
+                        malformed.skip(format_args!("record {index}"), e)
+                    }
+                    Err(e) => {
+                        malformed.skip(format_args!("record {index}"), e);
+                        return None;
+                    }
+                }
+            }
+        }))
+    }
+
+    fn malformed(&self) -> MalformedRows {
+        self.malformed.clone()
+    }
+}
+
+/// Check the file magic of `reader` and stream its records.
+pub fn read_binary_transactions(
+    mut reader: Box<dyn BufRead>,
+) -> Result<impl Iterator<Item = Result<Transaction, BinaryError>>, BinaryError> {
+    let mut magic = [0u8; MAGIC.len()];
+    match reader.read_exact(&mut magic) {
+        Ok(()) if &magic == MAGIC => {}
+        Ok(()) => return Err(BinaryError::BadMagic),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Err(BinaryError::BadMagic),
+        Err(e) => return Err(e.into()),
+    }
+
+    Ok(std::iter::from_fn(move || {
+        let mut record = [0u8; RECORD_SIZE];
+        match read_record(&mut reader, &mut record) {
+            Ok(false) => None,
+            Ok(true) => Some(decode(&record)),
+            Err(e) => Some(Err(e)),
+        }
+    }))
+}
+
+// Fill `record`, returning false at a clean end of input.
+fn read_record(
+    reader: &mut dyn BufRead,
+    record: &mut [u8; RECORD_SIZE],
+) -> Result<bool, BinaryError> {
+    let mut len = 0;
+    while len < RECORD_SIZE {
+        match reader.read(&mut record[len..]) {
+            Ok(0) if len == 0 => return Ok(false),
+            Ok(0) => return Err(BinaryError::TruncatedRecord),
+            Ok(n) => len += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transaction(r#type: TransactionType, tx: u32, amount: Option<Decimal>) -> Transaction {
+        Transaction {
+            r#type,
+            client: 513,
+            tx,
+            amount,
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let transactions = vec![
+            transaction(TransactionType::Deposit, 1, Some(Decimal::new(12345, 4))),
+            transaction(
+                TransactionType::Withdrawal,
+                u32::MAX,
+                Some(Decimal::new(-5, 1)),
+            ),
+            transaction(TransactionType::Dispute, 1, None),
+            transaction(TransactionType::Chargeback, 1, None),
+        ];
+
+        let mut writer = BinaryTransactionWriter::new(Vec::new()).unwrap();
+        for tx in &transactions {
+            writer.write(tx).unwrap();
+        }
+        let bytes = writer.finish().unwrap();
+        assert_eq!(bytes.len(), MAGIC.len() + transactions.len() * RECORD_SIZE);
+
+        let read: Vec<Transaction> =
+            BinaryTransactionSource::from_reader(std::io::Cursor::new(bytes))
+                .transactions()
+                .collect();
+        assert_eq!(read.len(), transactions.len());
+        for (read, written) in read.iter().zip(&transactions) {
+            assert_eq!(
+                (read.r#type, read.client, read.tx, read.amount),
+                (written.r#type, written.client, written.tx, written.amount)
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        let too_precise = transaction(TransactionType::Deposit, 1, Some(Decimal::new(1, 5)));
+        assert!(matches!(
+            encode(&too_precise),
+            Err(BinaryError::AmountOutOfRange(_))
+        ));
+
+        let mut record = encode(&transaction(TransactionType::Deposit, 1, None)).unwrap();
+        record[0] = 9;
+        assert!(matches!(decode(&record), Err(BinaryError::UnknownType(9))));
+
+        assert!(matches!(
+            read_binary_transactions(Box::new(&b"type,client"[..])),
+            Err(BinaryError::BadMagic)
+        ));
+        let mut truncated = read_binary_transactions(Box::new(&b"TXB1\x00\x01"[..])).unwrap();
+        assert!(matches!(
+            truncated.next(),
+            Some(Err(BinaryError::TruncatedRecord))
+        ));
+    }
+}
@@ -0,0 +1,104 @@
+use std::io::{self, Write};
+
+use thiserror::Error;
+
+use crate::binary::{BinaryError, BinaryTransactionWriter};
+use crate::report::Format;
+use crate::transaction::Transaction;
+
+/// ConvertError represents a transaction that cannot be written in the target format.
+#[derive(Debug, Error)]
+pub enum ConvertError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Binary(#[from] BinaryError),
+    #[error("Transactions cannot be written as bank statements.")]
+    Statement,
+}
+
+/// Write `transactions` to `writer` in `format` and return how many were written.
+/// CSV output uses the default `type,client,tx,amount` layout.
+pub fn write_transactions<I, W>(
+    transactions: I,
+    format: Format,
+    writer: W,
+) -> Result<u64, ConvertError>
+where
+    I: IntoIterator<Item = Transaction>,
+    W: Write,
+{
+    let mut count = 0;
+    match format {
+        Format::Csv => {
+            let mut csv = csv::Writer::from_writer(writer);
+            for transaction in transactions {
+                csv.serialize(&transaction)?;
+                count += 1;
+            }
+            csv.flush()?;
+        }
+        Format::JsonLines => {
+            let mut writer = writer;
+            for transaction in transactions {
+                serde_json::to_writer(&mut writer, &transaction)?;
+                writeln!(writer)?;
+                count += 1;
+            }
+            writer.flush()?;
+        }
+        Format::Binary => {
+            let mut binary = BinaryTransactionWriter::new(writer)?;
+            for transaction in transactions {
+                binary.write(&transaction)?;
+                count += 1;
+            }
+            binary.finish()?;
+        }
+        Format::Ofx | Format::Camt053 => return Err(ConvertError::Statement),
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::BinaryTransactionSource;
+    use crate::transaction::{CsvTransactionSource, TransactionSource};
+
+    const CSV: &str = "type,client,tx,amount\n\
+                       deposit,1,1,1.5000\n\
+                       withdrawal,2,2,0.25\n\
+                       dispute,1,1,\n";
+
+    #[test]
+    fn test_csv_to_binary_and_back() {
+        let mut binary = Vec::new();
+        let written = write_transactions(
+            CsvTransactionSource::from_reader(CSV.as_bytes()).transactions(),
+            Format::Binary,
+            &mut binary,
+        )
+        .unwrap();
+        assert_eq!(written, 3);
+
+        let mut csv = Vec::new();
+        write_transactions(
+            BinaryTransactionSource::from_reader(io::Cursor::new(binary)).transactions(),
+            Format::Csv,
+            &mut csv,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "type,client,tx,amount\n\
+             deposit,1,1,1.5000\n\
+             withdrawal,2,2,0.2500\n\
+             dispute,1,1,\n"
+        );
+    }
+}
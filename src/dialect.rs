@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+
+use csv::{ReaderBuilder, StringRecord};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::transaction::{Transaction, TransactionType};
+
+/// CsvDialect describes how a partner's CSV files are laid out: separators, column
+/// names and the names used for transaction types. The default matches the
+/// `type, client, tx, amount` layout with comma separators and `.` decimal marks.
+///
+/// It can be loaded from TOML:
+///
+/// ```toml
+/// delimiter = ";"
+/// decimal_separator = ","
+///
+/// [columns]
+/// type = "kind"
+/// client = "customer_id"
+/// tx = "txid"
+/// amount = "value"
+///
+/// [type_aliases]
+/// credit = "deposit"
+/// debit = "withdrawal"
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CsvDialect {
+    pub delimiter: char,
+    pub quote: char,
+    pub decimal_separator: char,
+    pub columns: ColumnMapping,
+    /// Extra names for transaction types, e.g. `credit = "deposit"`. Matched case-insensitively.
+    pub type_aliases: HashMap<String, TransactionType>,
+}
+
+/// ColumnMapping names the CSV column holding each `Transaction` field.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColumnMapping {
+    pub r#type: String,
+    pub client: String,
+    pub tx: String,
+    pub amount: String,
+}
+
+impl Default for ColumnMapping {
+    fn default() -> Self {
+        Self {
+            r#type: "type".to_string(),
+            client: "client".to_string(),
+            tx: "tx".to_string(),
+            amount: "amount".to_string(),
+        }
+    }
+}
+
+impl Default for CsvDialect {
+    fn default() -> Self {
+        Self {
+            delimiter: ',',
+            quote: '"',
+            decimal_separator: '.',
+            columns: ColumnMapping::default(),
+            type_aliases: HashMap::new(),
+        }
+    }
+}
+
+impl CsvDialect {
+    /// Load a dialect from a TOML file. Missing keys keep their default values.
+    pub fn from_toml_file(path: impl AsRef<Path>) -> Result<Self, DialectError> {
+        std::fs::read_to_string(path)?.parse()
+    }
+
+    /// A CSV reader builder for this dialect. Fields are trimmed and rows may omit
+    /// trailing fields, such as the amount of a dispute.
+    pub fn reader_builder(&self) -> ReaderBuilder {
+        let mut builder = ReaderBuilder::new();
+        builder
+            .delimiter(self.delimiter as u8)
+            .quote(self.quote as u8)
+            .trim(csv::Trim::All)
+            .flexible(true);
+        builder
+    }
+
+    /// Resolve the mapped columns in `headers` into a parser for data rows.
+    pub fn row_parser(&self, headers: &StringRecord) -> Result<RowParser, ParseError> {
+        let find = |name: &str| {
+            headers
+                .iter()
+                .position(|header| header.trim().eq_ignore_ascii_case(name))
+        };
+        let require =
+            |name: &str| find(name).ok_or_else(|| ParseError::MissingColumn(name.to_string()));
+
+        let mut type_names: HashMap<String, TransactionType> = TransactionType::ALL
+            .into_iter()
+            .map(|r#type| (r#type.name().to_string(), r#type))
+            .collect();
+        for (alias, r#type) in &self.type_aliases {
+            type_names.insert(alias.to_lowercase(), *r#type);
+        }
+
+        Ok(RowParser {
+            type_index: require(&self.columns.r#type)?,
+            client_index: require(&self.columns.client)?,
+            tx_index: require(&self.columns.tx)?,
+            amount_index: find(&self.columns.amount),
+            decimal_separator: self.decimal_separator,
+            type_names,
+        })
+    }
+
+    fn validate(&self) -> Result<(), DialectError> {
+        for (name, value) in [("delimiter", self.delimiter), ("quote", self.quote)] {
+            if !value.is_ascii() {
+                return Err(DialectError::NotAscii(name, value));
+            }
+        }
+        if self.delimiter == self.decimal_separator {
+            return Err(DialectError::AmbiguousSeparator(self.delimiter));
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for CsvDialect {
+    type Err = DialectError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let dialect: CsvDialect = toml::from_str(s)?;
+        dialect.validate()?;
+        Ok(dialect)
+    }
+}
+
+/// RowParser turns CSV records into transactions for one set of headers.
+#[derive(Debug, Clone)]
+pub struct RowParser {
+    type_index: usize,
+    client_index: usize,
+    tx_index: usize,
+    amount_index: Option<usize>,
+    decimal_separator: char,
+    // Lowercased type names and aliases.
+    type_names: HashMap<String, TransactionType>,
+}
+
+impl RowParser {
+    pub fn parse(&self, record: &StringRecord) -> Result<Transaction, ParseError> {
+        let field = |index: usize| record.get(index).unwrap_or_default().trim();
+
+        let type_name = field(self.type_index);
+        let r#type = *self
+            .type_names
+            .get(&type_name.to_lowercase())
+            .ok_or_else(|| ParseError::UnknownType(type_name.to_string()))?;
+
+        let client = field(self.client_index);
+        let client = client
+            .parse()
+            .map_err(|_| ParseError::invalid("client", client))?;
+        let tx = field(self.tx_index);
+        let tx = tx.parse().map_err(|_| ParseError::invalid("tx", tx))?;
+
+        let amount = match self.amount_index.map(field) {
+            None | Some("") => None,
+            Some(amount) => Some(self.parse_amount(amount)?),
+        };
+
+        Ok(Transaction {
+            r#type,
+            client,
+            tx,
+            amount,
+        })
+    }
+
+    fn parse_amount(&self, amount: &str) -> Result<Decimal, ParseError> {
+        let normalized = if self.decimal_separator == '.' {
+            amount.to_string()
+        } else {
+            amount.replace(self.decimal_separator, ".")
+        };
+        Decimal::from_str(&normalized).map_err(|_| ParseError::invalid("amount", amount))
+    }
+}
+
+/// ParseError represents a CSV row that cannot be turned into a transaction.
+#[derive(Debug, Error)]
+pub enum ParseError {
+    #[error("Missing column '{0}'.")]
+    MissingColumn(String),
+    #[error("Unknown transaction type '{0}'.")]
+    UnknownType(String),
+    #[error("Invalid {field} '{value}'.")]
+    InvalidField { field: &'static str, value: String },
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+}
+
+impl ParseError {
+    fn invalid(field: &'static str, value: &str) -> Self {
+        ParseError::InvalidField {
+            field,
+            value: value.to_string(),
+        }
+    }
+}
+
+/// DialectError represents a dialect file that cannot be loaded.
+#[derive(Debug, Error)]
+pub enum DialectError {
+    #[error("Failed to read dialect file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid dialect file: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("The {0} must be a single ASCII character, got '{1}'.")]
+    NotAscii(&'static str, char),
+    #[error("The delimiter and decimal separator are both '{0}'.")]
+    AmbiguousSeparator(char),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARTNER_DIALECT: &str = r#"
+        delimiter = ";"
+        decimal_separator = ","
+
+        [columns]
+        type = "kind"
+        client = "customer_id"
+        tx = "txid"
+        amount = "value"
+
+        [type_aliases]
+        CREDIT = "deposit"
+        debit = "withdrawal"
+    "#;
+
+    fn parse_all(dialect: &CsvDialect, csv: &str) -> Vec<Result<Transaction, ParseError>> {
+        let mut reader = dialect.reader_builder().from_reader(csv.as_bytes());
+        let parser = dialect.row_parser(reader.headers().unwrap()).unwrap();
+        reader
+            .records()
+            .map(|record| parser.parse(&record.unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_partner_dialect_from_toml() {
+        let dialect: CsvDialect = PARTNER_DIALECT.parse().unwrap();
+        assert_eq!(dialect.delimiter, ';');
+        assert_eq!(dialect.quote, '"');
+
+        let rows = parse_all(
+            &dialect,
+            "txid; value; customer_id; kind\n\
+             1;\"1,5\"; 7; credit\n\
+             2; 0,25; 7; Debit\n\
+             1; ; 7; dispute\n\
+             3; 1; 7; refund\n",
+        );
+
+        let tx = rows[0].as_ref().unwrap();
+        assert_eq!(tx.r#type, TransactionType::Deposit);
+        assert_eq!((tx.client, tx.tx), (7, 1));
+        assert_eq!(tx.amount, Some(Decimal::new(15, 1)));
+        assert_eq!(
+            rows[1].as_ref().unwrap().r#type,
+            TransactionType::Withdrawal
+        );
+        assert_eq!(rows[1].as_ref().unwrap().amount, Some(Decimal::new(25, 2)));
+        assert_eq!(rows[2].as_ref().unwrap().amount, None);
+        assert!(matches!(rows[3], Err(ParseError::UnknownType(_))));
+    }
+
+    #[test]
+    fn test_default_dialect_keeps_exact_amounts() {
+        let rows = parse_all(
+            &CsvDialect::default(),
+            "type, client, tx, amount\ndeposit, 1, 1, 0.1000\ndispute, 1, 1\n",
+        );
+        assert_eq!(
+            rows[0].as_ref().unwrap().amount.unwrap().to_string(),
+            "0.1000"
+        );
+        assert_eq!(rows[1].as_ref().unwrap().amount, None);
+    }
+
+    #[test]
+    fn test_invalid_dialects_and_headers() {
+        assert!(matches!(
+            "delimiter = \",\"\ndecimal_separator = \",\"".parse::<CsvDialect>(),
+            Err(DialectError::AmbiguousSeparator(','))
+        ));
+        assert!(matches!(
+            "separator = \";\"".parse::<CsvDialect>(),
+            Err(DialectError::Toml(_))
+        ));
+        assert!(matches!(
+            CsvDialect::default().row_parser(&StringRecord::from(vec!["type", "client"])),
+            Err(ParseError::MissingColumn(column)) if column == "tx"
+        ));
+    }
+}
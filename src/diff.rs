@@ -0,0 +1,222 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use rust_decimal::Decimal;
+
+use crate::account::AccountSummary;
+use crate::snapshot::Snapshot;
+use crate::transaction::{DisputeState, TransactionRecord};
+
+/// AccountDiff is a client whose account differs between two runs. `before` or `after`
+/// is `None` when the client only has an account in one of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountDiff {
+    pub client: u16,
+    pub before: Option<AccountSummary>,
+    pub after: Option<AccountSummary>,
+}
+
+impl AccountDiff {
+    /// Change of the client's total funds.
+    pub fn impact(&self) -> Decimal {
+        let total = |summary: &Option<AccountSummary>| {
+            summary
+                .as_ref()
+                .map_or(Decimal::ZERO, |summary| summary.total)
+        };
+        total(&self.after) - total(&self.before)
+    }
+}
+
+/// DisputeDiff is a transaction whose dispute state differs between two runs. A state
+/// is `None` when the transaction was not recorded in that run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisputeDiff {
+    pub tx: u32,
+    pub client: u16,
+    pub before: Option<DisputeState>,
+    pub after: Option<DisputeState>,
+}
+
+/// EngineDiff lists every account and transaction that differs between two runs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EngineDiff {
+    pub accounts: Vec<AccountDiff>,
+    pub disputes: Vec<DisputeDiff>,
+}
+
+/// Compare two runs. Differences are ordered by client and tx id.
+pub fn diff(before: &Snapshot, after: &Snapshot) -> EngineDiff {
+    let mut accounts: BTreeMap<u16, (Option<&AccountSummary>, Option<&AccountSummary>)> =
+        BTreeMap::new();
+    for summary in &before.accounts {
+        accounts.entry(summary.client).or_default().0 = Some(summary);
+    }
+    for summary in &after.accounts {
+        accounts.entry(summary.client).or_default().1 = Some(summary);
+    }
+
+    let mut transactions: BTreeMap<u32, (Option<&TransactionRecord>, Option<&TransactionRecord>)> =
+        BTreeMap::new();
+    for record in &before.transactions {
+        transactions.entry(record.transaction.tx).or_default().0 = Some(record);
+    }
+    for record in &after.transactions {
+        transactions.entry(record.transaction.tx).or_default().1 = Some(record);
+    }
+
+    EngineDiff {
+        accounts: accounts
+            .into_iter()
+            .filter(|(_, (before, after))| before != after)
+            .map(|(client, (before, after))| AccountDiff {
+                client,
+                before: before.cloned(),
+                after: after.cloned(),
+            })
+            .collect(),
+        disputes: transactions
+            .into_iter()
+            .filter_map(|(tx, (before, after))| {
+                let state = |record: Option<&TransactionRecord>| record.map(|r| r.dispute_state);
+                let (before_state, after_state) = (state(before), state(after));
+                (before_state != after_state).then(|| DisputeDiff {
+                    tx,
+                    client: before.or(after).map_or(0, |r| r.transaction.client),
+                    before: before_state,
+                    after: after_state,
+                })
+            })
+            .collect(),
+    }
+}
+
+impl EngineDiff {
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty() && self.disputes.is_empty()
+    }
+
+    /// Net change of the total funds over all clients.
+    pub fn total_impact(&self) -> Decimal {
+        self.accounts.iter().map(AccountDiff::impact).sum()
+    }
+
+    /// Sum of the absolute changes of the total funds, so that gains and losses of
+    /// different clients do not cancel out.
+    pub fn gross_impact(&self) -> Decimal {
+        self.accounts.iter().map(|a| a.impact().abs()).sum()
+    }
+
+    /// Write the differences followed by a summary line.
+    pub fn write<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        for account in &self.accounts {
+            match (&account.before, &account.after) {
+                (Some(before), Some(after)) => writeln!(
+                    writer,
+                    "client {}: available {} -> {}, held {} -> {}, total {} -> {}, locked {} -> {}",
+                    account.client,
+                    before.available,
+                    after.available,
+                    before.held,
+                    after.held,
+                    before.total,
+                    after.total,
+                    before.locked,
+                    after.locked
+                )?,
+                (None, Some(after)) => writeln!(
+                    writer,
+                    "client {}: added with total {}",
+                    account.client, after.total
+                )?,
+                (Some(before), None) => writeln!(
+                    writer,
+                    "client {}: removed, total was {}",
+                    account.client, before.total
+                )?,
+                (None, None) => {}
+            }
+        }
+        for dispute in &self.disputes {
+            writeln!(
+                writer,
+                "tx {} (client {}): {} -> {}",
+                dispute.tx,
+                dispute.client,
+                state_name(dispute.before),
+                state_name(dispute.after)
+            )?;
+        }
+
+        let added = self.accounts.iter().filter(|a| a.before.is_none()).count();
+        let removed = self.accounts.iter().filter(|a| a.after.is_none()).count();
+        writeln!(
+            writer,
+            "{} accounts differ ({} added, {} removed), {} transactions differ, total impact {} (gross {})",
+            self.accounts.len(),
+            added,
+            removed,
+            self.disputes.len(),
+            self.total_impact(),
+            self.gross_impact()
+        )
+    }
+}
+
+fn state_name(state: Option<DisputeState>) -> &'static str {
+    match state {
+        None => "not recorded",
+        Some(state) => state.name(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Engine;
+    use crate::test_support::{apply_all, tx};
+    use crate::transaction::TransactionType;
+
+    #[test]
+    fn test_diff_accounts_and_disputes() {
+        use TransactionType::*;
+        let before = Snapshot::from_engine(&apply_all(
+            Engine::new(),
+            [
+                tx(Deposit, 1, 1, Some(500)),
+                tx(Deposit, 2, 2, Some(300)),
+                tx(Dispute, 1, 1, None),
+            ],
+        ));
+        let after = Snapshot::from_engine(&apply_all(
+            Engine::new(),
+            [
+                tx(Deposit, 1, 1, Some(500)),
+                tx(Dispute, 1, 1, None),
+                tx(Chargeback, 1, 1, None),
+                tx(Deposit, 3, 3, Some(100)),
+            ],
+        ));
+
+        let diff = diff(&before, &after);
+        assert_eq!(
+            diff.accounts.iter().map(|a| a.client).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(diff.total_impact(), Decimal::new(-700, 2));
+        assert_eq!(diff.gross_impact(), Decimal::new(900, 2));
+
+        let mut output = Vec::new();
+        diff.write(&mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client 1: available 0.00 -> 0.00, held 5.00 -> 0.00, total 5.00 -> 0.00, locked false -> true\n\
+             client 2: removed, total was 3.00\n\
+             client 3: added with total 1.00\n\
+             tx 1 (client 1): disputed -> charged_back\n\
+             tx 2 (client 2): none -> not recorded\n\
+             tx 3 (client 3): not recorded -> none\n\
+             3 accounts differ (1 added, 1 removed), 3 transactions differ, total impact -7.00 (gross 9.00)\n"
+        );
+    }
+}
@@ -1,9 +1,18 @@
 use std::collections::HashMap;
+use std::io::{self, Write};
+use std::time::Instant;
 
 use log::{info, warn};
 use rust_decimal::Decimal;
+use thiserror::Error;
+use tracing::info_span;
 
-use crate::account::Account;
+use crate::account::{Account, AccountError, AccountSummary};
+use crate::audit::{AuditEvent, AuditObserver};
+use crate::history::Balances;
+use crate::ledger::Ledger;
+use crate::metrics::Metrics;
+use crate::report::{self, Format};
 use crate::transaction::{
     DisputeState, Transaction, TransactionRecord, TransactionSource, TransactionType,
 };
@@ -11,6 +20,14 @@ use crate::transaction::{
 pub struct Engine {
     pub accounts: HashMap<u16, Account>,
     pub transactions: HashMap<u32, TransactionRecord>,
+    /// Balances behind the account balances, and their journal when enabled with
+    /// `with_journal`.
+    pub ledger: Ledger,
+    /// Every applied transaction in order, when enabled with `with_history`.
+    pub history: Option<Vec<Transaction>>,
+    /// Counts of processed and rejected transactions.
+    pub metrics: Metrics,
+    observers: Vec<Box<dyn AuditObserver>>,
 }
 
 impl Default for Engine {
@@ -24,213 +41,426 @@ impl Engine {
         Self {
             accounts: HashMap::new(),
             transactions: HashMap::new(),
+            ledger: Ledger::new(),
+            history: None,
+            metrics: Metrics::new(),
+            observers: Vec::new(),
         }
     }
 
+    /// Keep the applied transactions in order, e.g. for per-client statements.
+    pub fn with_history(mut self) -> Self {
+        self.history = Some(Vec::new());
+        self
+    }
+
+    /// Keep the journal of ledger postings, e.g. to undo transactions.
+    pub fn with_journal(mut self) -> Self {
+        self.ledger = self.ledger.with_journal();
+        self
+    }
+
+    /// Deliver the audit events of every following transaction to `observer`.
+    pub fn with_observer(mut self, observer: impl AuditObserver + 'static) -> Self {
+        self.add_observer(observer);
+        self
+    }
+
+    pub fn add_observer(&mut self, observer: impl AuditObserver + 'static) {
+        self.observers.push(Box::new(observer));
+    }
+
     pub fn process_transactions<T: TransactionSource>(&mut self, source: &mut T) {
+        let _span = info_span!("source", kind = std::any::type_name::<T>()).entered();
         for transaction in source.transactions() {
-            self.apply_transaction(transaction);
+            // Rejections are logged by apply_transaction, processing carries on.
+            let _ = self.apply_transaction(transaction);
         }
     }
 
-    pub fn apply_transaction(&mut self, transaction: Transaction) {
-        match transaction.r#type {
-            TransactionType::Deposit => {
-                if let Some(amount) = transaction.amount {
-                    self.handle_deposit(transaction.client, transaction.tx, amount);
+    /// Apply a single transaction. Rejected transactions leave the engine unchanged,
+    /// are logged as warnings and returned as an `EngineError`.
+    pub fn apply_transaction(&mut self, transaction: Transaction) -> Result<(), EngineError> {
+        let Transaction {
+            r#type, client, tx, ..
+        } = transaction;
+        let _span = info_span!("transaction", tx, client, "type" = r#type.name()).entered();
+        let started = Instant::now();
+        // Deposits and withdrawals cannot change held funds or lock an account, so their
+        // balances are only taken for observers.
+        let disputes = !matches!(
+            r#type,
+            TransactionType::Deposit | TransactionType::Withdrawal
+        );
+        let before = if disputes || !self.observers.is_empty() {
+            self.balances(client)
+        } else {
+            None
+        };
+        let result = match r#type {
+            TransactionType::Deposit => transaction
+                .amount
+                .ok_or(EngineError::MissingAmount(tx))
+                .and_then(|amount| check_amount(tx, amount))
+                .and_then(|amount| self.handle_deposit(client, tx, amount)),
+            TransactionType::Withdrawal => transaction
+                .amount
+                .ok_or(EngineError::MissingAmount(tx))
+                .and_then(|amount| check_amount(tx, amount))
+                .and_then(|amount| self.handle_withdrawal(client, tx, amount)),
+            TransactionType::Dispute => self.handle_dispute(client, tx),
+            TransactionType::Resolve => self.handle_resolve(client, tx),
+            TransactionType::Chargeback => self.handle_chargeback(client, tx),
+        };
+        let after = if disputes {
+            self.balances(client)
+        } else {
+            None
+        };
+        self.metrics.record(
+            r#type,
+            result.as_ref().map(|_| ()),
+            before.as_ref().filter(|_| disputes),
+            after.as_ref(),
+            started.elapsed(),
+        );
+        match &result {
+            Ok(()) => {
+                if !self.observers.is_empty() {
+                    self.audit_applied(r#type, client, tx, before);
                 }
-            }
-            TransactionType::Withdrawal => {
-                if let Some(amount) = transaction.amount {
-                    self.handle_withdrawal(transaction.client, transaction.tx, amount);
+                if let Some(history) = &mut self.history {
+                    history.push(transaction);
                 }
             }
-            TransactionType::Dispute => {
-                self.handle_dispute(transaction.client, transaction.tx);
-            }
-            TransactionType::Resolve => {
-                self.handle_resolve(transaction.client, transaction.tx);
-            }
-            TransactionType::Chargeback => {
-                self.handle_chargeback(transaction.client, transaction.tx);
+            Err(e) => {
+                warn!(
+                    "{:?} for client {} (tx {}) rejected: {}",
+                    r#type, client, tx, e
+                );
+                self.emit(AuditEvent::TransactionRejected {
+                    r#type,
+                    client,
+                    tx,
+                    reason: e.to_string(),
+                    balances: before,
+                });
             }
         }
+        result
     }
 
     // Generate a report of all accounts and their balances
     pub fn report(&self) {
         if !self.accounts.is_empty() {
-            println!("client, available, held, total, locked");
-            for (client_id, account) in &self.accounts {
-                println!(
-                    "{client_id}, {}, {}, {}, {}",
-                    account.get_available(),
-                    account.held,
-                    account.total,
-                    account.is_locked
-                );
-            }
+            self.write_report(&mut io::stdout().lock())
+                .expect("Failed to write report");
         } else {
             println!("Engine has no accounts to report.");
         }
     }
 
-    fn handle_deposit(&mut self, client: u16, tx: u32, amount: Decimal) {
-        let account = self.accounts.entry(client).or_default();
-        if account.deposit(tx, amount).is_ok() {
-            // Record the transaction if deposit is successful
-            self.transactions.insert(
-                tx,
-                TransactionRecord {
-                    transaction: Transaction {
-                        r#type: TransactionType::Deposit,
-                        client,
-                        tx,
-                        amount: Some(amount),
-                    },
-                    dispute_state: DisputeState::None,
-                },
-            );
+    /// Write the account report as CSV to `writer`, ordered by client id so that
+    /// output is stable between runs.
+    pub fn write_report<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        report::write_account_summaries(&self.account_summaries(), Format::Csv, writer)
+    }
+
+    /// Summaries of all accounts, ordered by client id.
+    pub fn account_summaries(&self) -> Vec<AccountSummary> {
+        let mut summaries: Vec<AccountSummary> =
+            self.accounts.values().map(AccountSummary::from).collect();
+        summaries.sort_by_key(|summary| summary.client);
+        summaries
+    }
+
+    fn balances(&self, client: u16) -> Option<Balances> {
+        self.accounts.get(&client).map(Balances::from)
+    }
+
+    fn emit(&mut self, event: AuditEvent) {
+        for observer in &mut self.observers {
+            observer.on_event(&event);
         }
     }
 
-    fn handle_withdrawal(&mut self, client: u16, tx: u32, amount: Decimal) {
-        if let Some(account) = self.accounts.get_mut(&client)
-            && account.withdraw(tx, amount).is_ok()
-        {
-            self.transactions.insert(
+    // Emit the events of a transaction that was just applied. `before` holds the
+    // balances of the client's account before the transaction.
+    fn audit_applied(
+        &mut self,
+        r#type: TransactionType,
+        client: u16,
+        tx: u32,
+        before: Option<Balances>,
+    ) {
+        let (Some(after), Some(amount)) = (
+            self.balances(client),
+            self.transactions
+                .get(&tx)
+                .and_then(|record| record.transaction.amount),
+        ) else {
+            return;
+        };
+        let event = match r#type {
+            TransactionType::Deposit | TransactionType::Withdrawal => {
+                AuditEvent::TransactionApplied {
+                    r#type,
+                    client,
+                    tx,
+                    amount,
+                    before,
+                    after,
+                }
+            }
+            TransactionType::Dispute => AuditEvent::DisputeOpened {
+                client,
                 tx,
-                TransactionRecord {
-                    transaction: Transaction {
-                        r#type: TransactionType::Withdrawal,
-                        client,
-                        tx,
-                        amount: Some(amount),
-                    },
-                    dispute_state: DisputeState::None,
-                },
-            );
+                amount,
+                before: before.unwrap_or_default(),
+                after,
+            },
+            TransactionType::Resolve => AuditEvent::DisputeResolved {
+                client,
+                tx,
+                amount,
+                before: before.unwrap_or_default(),
+                after,
+            },
+            TransactionType::Chargeback => {
+                let before = before.unwrap_or_default();
+                let locked = after.locked && !before.locked;
+                self.emit(AuditEvent::ChargebackApplied {
+                    client,
+                    tx,
+                    amount,
+                    before: before.clone(),
+                    after: after.clone(),
+                });
+                if !locked {
+                    return;
+                }
+                AuditEvent::AccountLocked {
+                    client,
+                    tx,
+                    before,
+                    after,
+                }
+            }
+        };
+        self.emit(event);
+    }
+
+    fn handle_deposit(&mut self, client: u16, tx: u32, amount: Decimal) -> Result<(), EngineError> {
+        if self.transactions.contains_key(&tx) {
+            return Err(EngineError::DuplicateTransaction(tx));
         }
+        let account = self
+            .accounts
+            .entry(client)
+            .or_insert_with(|| Account::new(client));
+        account.deposit(&mut self.ledger, tx, amount)?;
+
+        // Record the transaction only once the deposit is successful
+        self.record(TransactionType::Deposit, client, tx, amount);
+        Ok(())
+    }
+
+    fn handle_withdrawal(
+        &mut self,
+        client: u16,
+        tx: u32,
+        amount: Decimal,
+    ) -> Result<(), EngineError> {
+        if self.transactions.contains_key(&tx) {
+            return Err(EngineError::DuplicateTransaction(tx));
+        }
+        let account = self
+            .accounts
+            .get_mut(&client)
+            .ok_or(EngineError::ClientNotFound { client, tx })?;
+        account.withdraw(&mut self.ledger, tx, amount)?;
+
+        self.record(TransactionType::Withdrawal, client, tx, amount);
+        Ok(())
     }
 
     // In the envent of dispute, client claims that a transaction was erroneous and should be reversed.
     // Clients available funds should be decreased by teh amount disputed, their held funds should
     // increase by the amount disputed, while their total funds should remain the same
-    fn handle_dispute(&mut self, client: u16, tx: u32) {
-        if let Some(record) = self.transactions.get_mut(&tx) {
-            if record.dispute_state == DisputeState::None {
-                if let Some(account) = self.accounts.get_mut(&client) {
-                    if let Some(amount) = record.transaction.amount {
-                        match account.dispute(amount) {
-                            Ok(_) => {
-                                record.dispute_state = DisputeState::Disputed;
-                                info!(
-                                    "Dispute of {} for client {} processed. Held funds updated to {}.",
-                                    amount, client, account.held
-                                );
-                            }
-                            Err(e) => {
-                                warn!("Failed to process dispute for transaction {}: {}", tx, e);
-                            }
-                        }
-                    } else {
-                        warn!(
-                            "Transaction {} for client {} has no associated amount to dispute.",
-                            tx, client
-                        );
-                    }
-                } else {
-                    warn!("Client {} not found for transaction {}.", client, tx);
-                }
-            } else {
-                warn!(
-                    "Transaction {} for client {} is already in dispute.",
-                    tx, client
-                );
+    fn handle_dispute(&mut self, client: u16, tx: u32) -> Result<(), EngineError> {
+        let record = Self::client_record(&mut self.transactions, client, tx)?;
+        match record.dispute_state {
+            DisputeState::None => {}
+            DisputeState::Disputed => return Err(EngineError::AlreadyDisputed { client, tx }),
+            // A transaction is disputed at most once.
+            DisputeState::Resolved | DisputeState::ChargedBack => {
+                return Err(EngineError::DisputeClosed { client, tx });
             }
-        } else {
-            warn!("Transaction {} not found for client {}.", tx, client);
         }
+        let account = self
+            .accounts
+            .get_mut(&client)
+            .ok_or(EngineError::ClientNotFound { client, tx })?;
+        let amount = record
+            .transaction
+            .amount
+            .ok_or(EngineError::MissingAmount(tx))?;
+
+        account.dispute(&mut self.ledger, tx, amount)?;
+        record.dispute_state = DisputeState::Disputed;
+        info!(
+            "Dispute of {} for client {} processed. Held funds updated to {}.",
+            amount,
+            client,
+            account.held()
+        );
+        Ok(())
     }
 
     /// A resolve represents a resolution to a dispute, releasing the assotiated held funds. Funds that were
     /// previously disputed and no longer disputed. Held funds should be decreased by the disputed amount.
     /// Total should remain the same.
-    fn handle_resolve(&mut self, client: u16, tx: u32) {
-        if let Some(record) = self.transactions.get_mut(&tx) {
-            if record.dispute_state == DisputeState::Disputed {
-                if let Some(account) = self.accounts.get_mut(&client) {
-                    if let Some(amount) = record.transaction.amount {
-                        match account.resolve(amount) {
-                            Ok(_) => {
-                                record.dispute_state = DisputeState::Resolved;
-                                info!(
-                                    "Resolve of {} for client {} processed. Held funds updated to {}.",
-                                    amount, client, account.held
-                                );
-                            }
-                            Err(e) => {
-                                warn!("Failed to process resolve for transaction {}: {}", tx, e);
-                            }
-                        }
-                    } else {
-                        warn!(
-                            "Transaction {} for client {} has no associated amount to resolve.",
-                            tx, client
-                        );
-                    }
-                } else {
-                    warn!("Client {} not found for transaction {}.", client, tx);
-                }
-            } else {
-                warn!(
-                    "Transaction {} for client {} is not in dispute.",
-                    tx, client
-                );
-            }
-        } else {
-            warn!("Transaction {} not found for client {}.", tx, client);
+    fn handle_resolve(&mut self, client: u16, tx: u32) -> Result<(), EngineError> {
+        let record = Self::client_record(&mut self.transactions, client, tx)?;
+        if record.dispute_state != DisputeState::Disputed {
+            return Err(EngineError::NotDisputed { client, tx });
         }
+        let account = self
+            .accounts
+            .get_mut(&client)
+            .ok_or(EngineError::ClientNotFound { client, tx })?;
+        let amount = record
+            .transaction
+            .amount
+            .ok_or(EngineError::MissingAmount(tx))?;
+
+        account.resolve(&mut self.ledger, tx, amount)?;
+        record.dispute_state = DisputeState::Resolved;
+        info!(
+            "Resolve of {} for client {} processed. Held funds updated to {}.",
+            amount,
+            client,
+            account.held()
+        );
+        Ok(())
     }
 
     /// A chargeback is the final state of a dispute and represents the client reversing a transaction.
     /// Funds that were held have now been withdrawn. This means that the clients fheld funds and total funds
     /// should decreaseby the amount previously disputed.
     /// If a chargeback occurs the client account should be immediately frozen.
-    fn handle_chargeback(&mut self, client: u16, tx: u32) {
-        if let Some(record) = self.transactions.get_mut(&tx) {
-            if record.dispute_state == DisputeState::Disputed {
-                if let Some(account) = self.accounts.get_mut(&client) {
-                    if let Some(amount) = record.transaction.amount {
-                        match account.chargeback(amount) {
-                            Ok(_) => {
-                                record.dispute_state = DisputeState::ChargedBack;
-                                info!(
-                                    "Chargeback of {} for client {} processed. Account locked.",
-                                    amount, client
-                                );
-                            }
-                            Err(e) => {
-                                warn!("Failed to process chargeback for transaction {}: {}", tx, e);
-                            }
-                        }
-                    } else {
-                        warn!(
-                            "Transaction {} for client {} has no associated amount to chargeback.",
-                            tx, client
-                        );
-                    }
-                } else {
-                    warn!("Client {} not found for transaction {}.", client, tx);
-                }
-            } else {
-                warn!(
-                    "Transaction {} for client {} is not in dispute.",
-                    tx, client
-                );
-            }
-        } else {
-            warn!("Transaction {} not found for client {}.", tx, client);
+    fn handle_chargeback(&mut self, client: u16, tx: u32) -> Result<(), EngineError> {
+        let record = Self::client_record(&mut self.transactions, client, tx)?;
+        if record.dispute_state != DisputeState::Disputed {
+            return Err(EngineError::NotDisputed { client, tx });
+        }
+        let account = self
+            .accounts
+            .get_mut(&client)
+            .ok_or(EngineError::ClientNotFound { client, tx })?;
+        let amount = record
+            .transaction
+            .amount
+            .ok_or(EngineError::MissingAmount(tx))?;
+
+        account.chargeback(&mut self.ledger, tx, amount)?;
+        record.dispute_state = DisputeState::ChargedBack;
+        info!(
+            "Chargeback of {} for client {} processed. Account locked.",
+            amount, client
+        );
+        Ok(())
+    }
+
+    // Look up a recorded transaction that a dispute, resolve or chargeback refers to.
+    // The transaction must belong to the client making the claim.
+    fn client_record(
+        transactions: &mut HashMap<u32, TransactionRecord>,
+        client: u16,
+        tx: u32,
+    ) -> Result<&mut TransactionRecord, EngineError> {
+        let record = transactions
+            .get_mut(&tx)
+            .ok_or(EngineError::TransactionNotFound { client, tx })?;
+        if record.transaction.client != client {
+            return Err(EngineError::WrongClient { client, tx });
+        }
+        Ok(record)
+    }
+
+    fn record(&mut self, r#type: TransactionType, client: u16, tx: u32, amount: Decimal) {
+        self.transactions.insert(
+            tx,
+            TransactionRecord {
+                transaction: Transaction {
+                    r#type,
+                    client,
+                    tx,
+                    amount: Some(amount),
+                },
+                dispute_state: DisputeState::None,
+            },
+        );
+    }
+}
+
+/// Number of decimal places deposit and withdrawal amounts may have. Amounts are also
+/// stored at this scale in the binary format.
+pub const AMOUNT_SCALE: u32 = 4;
+
+// Amounts must be non-negative with at most AMOUNT_SCALE decimal places in the range of
+// an i64 once scaled, so that balances are never rounded and cannot overflow.
+fn check_amount(tx: u32, amount: Decimal) -> Result<Decimal, EngineError> {
+    let mut scaled = amount;
+    scaled.rescale(AMOUNT_SCALE);
+    if amount.is_sign_negative() || scaled != amount || i64::try_from(scaled.mantissa()).is_err() {
+        return Err(EngineError::InvalidAmount { tx, amount });
+    }
+    Ok(amount)
+}
+
+/// EngineError represents the reasons the engine rejects a transaction.
+#[derive(Debug, Error)]
+pub enum EngineError {
+    #[error("Transaction {0} has no amount.")]
+    MissingAmount(u32),
+    #[error(
+        "Amount {amount} of transaction {tx} is negative or has more than {AMOUNT_SCALE} decimal places."
+    )]
+    InvalidAmount { tx: u32, amount: Decimal },
+    #[error("Transaction {0} already exists.")]
+    DuplicateTransaction(u32),
+    #[error("Client {client} not found for transaction {tx}.")]
+    ClientNotFound { client: u16, tx: u32 },
+    #[error("Transaction {tx} not found for client {client}.")]
+    TransactionNotFound { client: u16, tx: u32 },
+    #[error("Transaction {tx} does not belong to client {client}.")]
+    WrongClient { client: u16, tx: u32 },
+    #[error("Transaction {tx} for client {client} is already in dispute.")]
+    AlreadyDisputed { client: u16, tx: u32 },
+    #[error("The dispute of transaction {tx} for client {client} is already closed.")]
+    DisputeClosed { client: u16, tx: u32 },
+    #[error("Transaction {tx} for client {client} is not in dispute.")]
+    NotDisputed { client: u16, tx: u32 },
+    #[error(transparent)]
+    Account(#[from] AccountError),
+}
+
+impl EngineError {
+    /// Stable snake_case name of the rejection reason, e.g. for API errors and metrics.
+    pub fn code(&self) -> &'static str {
+        match self {
+            EngineError::MissingAmount(_) => "missing_amount",
+            EngineError::InvalidAmount { .. } => "invalid_amount",
+            EngineError::DuplicateTransaction(_) => "duplicate_transaction",
+            EngineError::ClientNotFound { .. } => "client_not_found",
+            EngineError::TransactionNotFound { .. } => "transaction_not_found",
+            EngineError::WrongClient { .. } => "wrong_client",
+            EngineError::AlreadyDisputed { .. } => "already_disputed",
+            EngineError::DisputeClosed { .. } => "dispute_closed",
+            EngineError::NotDisputed { .. } => "not_disputed",
+            EngineError::Account(AccountError::AccountLocked(_)) => "account_locked",
+            EngineError::Account(AccountError::InsufficientFunds(_)) => "insufficient_funds",
         }
     }
 }
@@ -238,11 +468,12 @@ impl Engine {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ledger::LedgerAccount;
     use rust_decimal::Decimal;
 
     fn setup_engine_with_deposit(client_id: u16, tx_id: u32, amount: Decimal) -> Engine {
         let mut engine = Engine::new();
-        engine.handle_deposit(client_id, tx_id, amount);
+        engine.handle_deposit(client_id, tx_id, amount).unwrap();
         engine
     }
 
@@ -255,9 +486,9 @@ mod tests {
         let mut engine = setup_engine_with_deposit(client_id, tx_id, deposit_amount);
 
         // Dispute the transaction
-        engine.handle_dispute(client_id, tx_id);
+        engine.handle_dispute(client_id, tx_id).unwrap();
         let account = engine.accounts.get(&client_id).unwrap();
-        assert_eq!(account.held, deposit_amount);
+        assert_eq!(account.held(), deposit_amount);
         assert_eq!(account.get_available(), Decimal::ZERO);
 
         // Verify transaction state
@@ -274,12 +505,12 @@ mod tests {
         let mut engine = setup_engine_with_deposit(client_id, tx_id, deposit_amount);
 
         // Dispute the transaction
-        engine.handle_dispute(client_id, tx_id);
+        engine.handle_dispute(client_id, tx_id).unwrap();
 
         // Resolve the dispute
-        engine.handle_resolve(client_id, tx_id);
+        engine.handle_resolve(client_id, tx_id).unwrap();
         let account = engine.accounts.get(&client_id).unwrap();
-        assert_eq!(account.held, Decimal::ZERO);
+        assert_eq!(account.held(), Decimal::ZERO);
         assert_eq!(account.get_available(), deposit_amount);
 
         // Verify transaction state
@@ -296,16 +527,16 @@ mod tests {
         let mut engine = setup_engine_with_deposit(client_id, tx_id, deposit_amount);
 
         // Dispute the transaction
-        engine.handle_dispute(client_id, tx_id);
+        engine.handle_dispute(client_id, tx_id).unwrap();
         let account = engine.accounts.get(&client_id).unwrap();
-        assert_eq!(account.held, deposit_amount);
+        assert_eq!(account.held(), deposit_amount);
         assert_eq!(account.get_available(), Decimal::ZERO);
 
         // Chargeback the transaction
-        engine.handle_chargeback(client_id, tx_id);
+        engine.handle_chargeback(client_id, tx_id).unwrap();
         let account = engine.accounts.get(&client_id).unwrap();
-        assert_eq!(account.total, Decimal::ZERO);
-        assert_eq!(account.held, Decimal::ZERO);
+        assert_eq!(account.total(), Decimal::ZERO);
+        assert_eq!(account.held(), Decimal::ZERO);
         assert!(account.is_locked);
 
         // Verify transaction state
@@ -314,11 +545,223 @@ mod tests {
 
         // Edge case: Chargeback a non-existent transaction
         let non_existent_tx_id = 9999;
-        engine.handle_chargeback(client_id, non_existent_tx_id);
-        // No panic or crash expected, just a warning log
+        assert!(matches!(
+            engine.handle_chargeback(client_id, non_existent_tx_id),
+            Err(EngineError::TransactionNotFound { .. })
+        ));
 
         // Edge case: Chargeback a transaction not in dispute
-        engine.handle_chargeback(client_id, tx_id);
-        // No state change expected, just a warning log
+        assert!(matches!(
+            engine.handle_chargeback(client_id, tx_id),
+            Err(EngineError::NotDisputed { .. })
+        ));
+
+        // Edge case: Dispute a transaction whose dispute is closed
+        assert!(matches!(
+            engine.handle_dispute(client_id, tx_id),
+            Err(EngineError::DisputeClosed { .. })
+        ));
+    }
+
+    #[test]
+    fn test_ledger_trial_balance_sums_to_zero() {
+        let mut engine = Engine::new().with_journal();
+        let rows = [
+            (TransactionType::Deposit, 1, 1, Some(Decimal::new(500, 2))),
+            (TransactionType::Deposit, 2, 2, Some(Decimal::new(300, 2))),
+            (
+                TransactionType::Withdrawal,
+                1,
+                3,
+                Some(Decimal::new(200, 2)),
+            ),
+            (TransactionType::Dispute, 1, 1, None),
+            (
+                TransactionType::Withdrawal,
+                1,
+                4,
+                Some(Decimal::new(100, 2)),
+            ),
+            (TransactionType::Chargeback, 1, 1, None),
+            (TransactionType::Dispute, 2, 2, None),
+            (TransactionType::Resolve, 2, 2, None),
+        ];
+        for (r#type, client, tx, amount) in rows {
+            let _ = engine.apply_transaction(Transaction {
+                r#type,
+                client,
+                tx,
+                amount,
+            });
+            assert_eq!(engine.ledger.trial_balance().total(), Decimal::ZERO);
+        }
+
+        // Account balances are the client ledger accounts, and settlement clearing
+        // holds the opposite of what is owed to all clients and lost to chargebacks.
+        let mut owed = Decimal::ZERO;
+        for (client, account) in &engine.accounts {
+            assert_eq!(
+                account.get_available(),
+                engine
+                    .ledger
+                    .balance(LedgerAccount::ClientAvailable(*client))
+            );
+            assert_eq!(
+                account.held(),
+                engine.ledger.balance(LedgerAccount::ClientHeld(*client))
+            );
+            owed += account.total();
+        }
+        let lost = engine.ledger.balance(LedgerAccount::ChargebackLoss);
+        assert_eq!(lost, Decimal::new(500, 2));
+        assert_eq!(
+            engine.ledger.balance(LedgerAccount::SettlementClearing),
+            -owed - lost
+        );
+        // The rejected withdrawal posted nothing.
+        assert_eq!(engine.ledger.journal().len(), 7);
+    }
+
+    #[test]
+    fn test_duplicate_tx_id_rejected() {
+        let mut engine = setup_engine_with_deposit(1, 1001, Decimal::new(100, 2));
+
+        // Same tx id for the same client and for another client
+        assert!(
+            engine
+                .handle_deposit(1, 1001, Decimal::new(500, 2))
+                .is_err()
+        );
+        assert!(
+            engine
+                .handle_deposit(2, 1001, Decimal::new(500, 2))
+                .is_err()
+        );
+        assert!(
+            engine
+                .handle_withdrawal(1, 1001, Decimal::new(50, 2))
+                .is_err()
+        );
+
+        let account = engine.accounts.get(&1).unwrap();
+        assert_eq!(account.total(), Decimal::new(100, 2));
+        assert!(!engine.accounts.contains_key(&2));
+        assert_eq!(
+            engine.transactions.get(&1001).unwrap().transaction.client,
+            1
+        );
+    }
+
+    #[test]
+    fn test_dispute_from_other_client_ignored() {
+        let mut engine = setup_engine_with_deposit(1, 1001, Decimal::new(100, 2));
+        engine
+            .handle_deposit(2, 1002, Decimal::new(300, 2))
+            .unwrap();
+
+        // Client 2 disputes a transaction that belongs to client 1
+        assert!(matches!(
+            engine.handle_dispute(2, 1001),
+            Err(EngineError::WrongClient {
+                client: 2,
+                tx: 1001
+            })
+        ));
+        assert_eq!(engine.accounts.get(&1).unwrap().held(), Decimal::ZERO);
+        assert_eq!(engine.accounts.get(&2).unwrap().held(), Decimal::ZERO);
+        assert_eq!(
+            engine.transactions.get(&1001).unwrap().dispute_state,
+            DisputeState::None
+        );
+    }
+
+    #[test]
+    fn test_write_report_sorted_by_client() {
+        let mut engine = Engine::new();
+        engine.handle_deposit(3, 1, Decimal::new(300, 2)).unwrap();
+        engine.handle_deposit(1, 2, Decimal::new(100, 2)).unwrap();
+        engine.handle_deposit(2, 3, Decimal::new(200, 2)).unwrap();
+
+        let mut output = Vec::new();
+        engine.write_report(&mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client, available, held, total, locked\n\
+             1, 1.00, 0, 1.00, false\n\
+             2, 2.00, 0, 2.00, false\n\
+             3, 3.00, 0, 3.00, false\n"
+        );
+    }
+
+    #[test]
+    fn test_apply_transaction_missing_amount() {
+        let mut engine = Engine::new();
+        let result = engine.apply_transaction(Transaction {
+            r#type: TransactionType::Deposit,
+            client: 1,
+            tx: 1,
+            amount: None,
+        });
+        assert!(matches!(result, Err(EngineError::MissingAmount(1))));
+        assert!(engine.accounts.is_empty());
+    }
+
+    #[test]
+    fn test_check_amount() {
+        let max = Decimal::new(i64::MAX, AMOUNT_SCALE);
+        for amount in [
+            Decimal::ZERO,
+            Decimal::new(15, 1),
+            Decimal::new(1, AMOUNT_SCALE),
+            // Trailing zeros beyond the scale lose nothing.
+            Decimal::new(10, AMOUNT_SCALE + 1),
+            max,
+        ] {
+            assert_eq!(check_amount(7, amount).unwrap(), amount);
+        }
+        for amount in [
+            Decimal::new(-1, AMOUNT_SCALE),
+            Decimal::new(1, AMOUNT_SCALE + 1),
+            max + Decimal::new(1, AMOUNT_SCALE),
+            Decimal::MAX,
+        ] {
+            assert!(matches!(
+                check_amount(7, amount),
+                Err(EngineError::InvalidAmount { tx: 7, amount: a }) if a == amount
+            ));
+        }
+    }
+
+    #[test]
+    fn test_apply_transaction_invalid_amount() {
+        let mut engine = Engine::new();
+        for amount in [
+            Decimal::new(-1, 0),
+            Decimal::new(1, 5),
+            Decimal::from(i64::MAX),
+            Decimal::MAX,
+        ] {
+            let result = engine.apply_transaction(Transaction {
+                r#type: TransactionType::Deposit,
+                client: 1,
+                tx: 1,
+                amount: Some(amount),
+            });
+            assert!(matches!(
+                result,
+                Err(EngineError::InvalidAmount { tx: 1, .. })
+            ));
+        }
+        assert!(engine.accounts.is_empty());
+        assert_eq!(engine.metrics.rejected("invalid_amount"), 4);
+
+        let amount = Some(Decimal::new(i64::MAX, 4));
+        let deposit = Transaction {
+            r#type: TransactionType::Deposit,
+            client: 1,
+            tx: 2,
+            amount,
+        };
+        assert!(engine.apply_transaction(deposit).is_ok());
     }
 }
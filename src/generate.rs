@@ -0,0 +1,530 @@
+//! Synthetic workloads for load testing and benchmarks.
+//!
+//! Rows are generated against a model of every client's account, so that apart from
+//! the deliberate faults every row is accepted by the engine: withdrawals stay within
+//! the available funds, disputes refer to recent deposits of the same client, and
+//! resolves and chargebacks to its open disputes. The same configuration and seed
+//! always produce the same rows.
+
+use std::collections::VecDeque;
+use std::str::FromStr;
+
+use rust_decimal::Decimal;
+use thiserror::Error;
+
+use crate::engine::AMOUNT_SCALE;
+use crate::transaction::{Transaction, TransactionType};
+
+// Deposits per client kept as dispute candidates.
+const RECENT_DEPOSITS: usize = 16;
+// Largest deposit, in units of the fourth decimal place.
+const MAX_DEPOSIT: i64 = 10_000_000;
+
+/// Mix is the relative weight of each transaction type. A dispute, resolve or
+/// chargeback without anything to refer to is generated as a deposit instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mix {
+    pub deposit: u32,
+    pub withdrawal: u32,
+    pub dispute: u32,
+    pub resolve: u32,
+    pub chargeback: u32,
+}
+
+impl Default for Mix {
+    fn default() -> Self {
+        Self {
+            deposit: 60,
+            withdrawal: 25,
+            dispute: 8,
+            resolve: 5,
+            chargeback: 2,
+        }
+    }
+}
+
+impl FromStr for Mix {
+    type Err = MixError;
+
+    /// Parse `type=weight` pairs separated by commas, e.g. `deposit=70,withdrawal=30`.
+    /// Types that are not listed get weight 0.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut mix = Mix {
+            deposit: 0,
+            withdrawal: 0,
+            dispute: 0,
+            resolve: 0,
+            chargeback: 0,
+        };
+        for pair in s.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+            let (name, weight) = pair
+                .split_once('=')
+                .ok_or_else(|| MixError::Invalid(pair.to_string()))?;
+            let weight = weight
+                .trim()
+                .parse()
+                .map_err(|_| MixError::Invalid(pair.to_string()))?;
+            match name.trim() {
+                "deposit" => mix.deposit = weight,
+                "withdrawal" => mix.withdrawal = weight,
+                "dispute" => mix.dispute = weight,
+                "resolve" => mix.resolve = weight,
+                "chargeback" => mix.chargeback = weight,
+                other => return Err(MixError::UnknownType(other.to_string())),
+            }
+        }
+        if mix.total() == 0 {
+            return Err(MixError::Empty);
+        }
+        Ok(mix)
+    }
+}
+
+impl Mix {
+    fn total(&self) -> u64 {
+        [
+            self.deposit,
+            self.withdrawal,
+            self.dispute,
+            self.resolve,
+            self.chargeback,
+        ]
+        .iter()
+        .map(|&weight| u64::from(weight))
+        .sum()
+    }
+
+    fn pick(&self, roll: u64) -> TransactionType {
+        let mut roll = roll % self.total();
+        for (weight, r#type) in [
+            (self.deposit, TransactionType::Deposit),
+            (self.withdrawal, TransactionType::Withdrawal),
+            (self.dispute, TransactionType::Dispute),
+            (self.resolve, TransactionType::Resolve),
+        ] {
+            if roll < u64::from(weight) {
+                return r#type;
+            }
+            roll -= u64::from(weight);
+        }
+        TransactionType::Chargeback
+    }
+}
+
+/// MixError represents a transaction mix that cannot be parsed.
+#[derive(Debug, Error)]
+pub enum MixError {
+    #[error("Invalid weight '{0}', expected <type>=<weight>.")]
+    Invalid(String),
+    #[error("Unknown transaction type '{0}'.")]
+    UnknownType(String),
+    #[error("The mix needs at least one non-zero weight.")]
+    Empty,
+}
+
+/// Fault is a kind of row the engine is expected to reject.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// A dispute, resolve or chargeback of a tx id that was never used.
+    UnknownTx,
+    /// A dispute of another client's deposit.
+    WrongClient,
+    /// A deposit reusing a tx id.
+    Duplicate,
+    /// A deposit to an account locked by a chargeback.
+    LockedAccount,
+}
+
+impl Fault {
+    pub const ALL: [Fault; 4] = [
+        Fault::UnknownTx,
+        Fault::WrongClient,
+        Fault::Duplicate,
+        Fault::LockedAccount,
+    ];
+
+    /// The `EngineError::code` the engine rejects the row with.
+    pub fn code(self) -> &'static str {
+        match self {
+            Fault::UnknownTx => "transaction_not_found",
+            Fault::WrongClient => "wrong_client",
+            Fault::Duplicate => "duplicate_transaction",
+            Fault::LockedAccount => "account_locked",
+        }
+    }
+}
+
+/// WorkloadConfig describes the rows a `Workload` generates.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkloadConfig {
+    /// Number of rows.
+    pub transactions: u64,
+    /// Number of clients, with ids from 1.
+    pub clients: u16,
+    pub mix: Mix,
+    /// Share of rows, between 0 and 1, replaced by a fault.
+    pub fault_rate: f64,
+    pub seed: u64,
+}
+
+impl Default for WorkloadConfig {
+    fn default() -> Self {
+        Self {
+            transactions: 1000,
+            clients: 100,
+            mix: Mix::default(),
+            fault_rate: 0.01,
+            seed: 42,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct ClientState {
+    // Funds in units of the fourth decimal place.
+    available: i64,
+    // Undisputed deposits that can still be disputed, oldest first.
+    deposits: VecDeque<(u32, i64)>,
+    disputed: Vec<(u32, i64)>,
+    locked: bool,
+}
+
+/// Workload generates the rows of a `WorkloadConfig` as an iterator.
+pub struct Workload {
+    config: WorkloadConfig,
+    rng: SplitMix64,
+    generated: u64,
+    clients: Vec<ClientState>,
+    // Clients that are not locked, and the position of each client in it.
+    unlocked: Vec<u16>,
+    unlocked_position: Vec<Option<usize>>,
+    next_tx: u32,
+    // Ids of rows that are never recorded count down from the top, so that every id
+    // below `next_tx` belongs to a recorded deposit or withdrawal.
+    next_unrecorded_tx: u32,
+    faults: [u64; Fault::ALL.len()],
+}
+
+impl Workload {
+    pub fn new(config: WorkloadConfig) -> Self {
+        let clients = usize::from(config.clients.max(1));
+        Self {
+            rng: SplitMix64(config.seed),
+            config,
+            generated: 0,
+            clients: (0..clients).map(|_| ClientState::default()).collect(),
+            unlocked: (1..=clients as u16).collect(),
+            unlocked_position: (0..clients).map(Some).collect(),
+            next_tx: 1,
+            next_unrecorded_tx: u32::MAX,
+            faults: [0; Fault::ALL.len()],
+        }
+    }
+
+    /// Faults of the given kind generated so far.
+    pub fn faults(&self, fault: Fault) -> u64 {
+        self.faults[fault as usize]
+    }
+
+    fn client(&mut self, client: u16) -> &mut ClientState {
+        &mut self.clients[usize::from(client - 1)]
+    }
+
+    fn random_client(&mut self) -> u16 {
+        self.rng.below(self.clients.len() as u64) as u16 + 1
+    }
+
+    fn random_unlocked_client(&mut self) -> u16 {
+        let index = self.rng.below(self.unlocked.len() as u64);
+        self.unlocked[index as usize]
+    }
+
+    fn new_tx(&mut self) -> u32 {
+        let tx = self.next_tx;
+        self.next_tx += 1;
+        tx
+    }
+
+    fn unrecorded_tx(&mut self) -> u32 {
+        let tx = self.next_unrecorded_tx;
+        self.next_unrecorded_tx -= 1;
+        tx
+    }
+
+    fn deposit(&mut self) -> Transaction {
+        let client = self.random_unlocked_client();
+        let amount = self.rng.below(MAX_DEPOSIT as u64) as i64 + 1;
+        let tx = self.new_tx();
+        let state = self.client(client);
+        state.available += amount;
+        state.deposits.push_back((tx, amount));
+        if state.deposits.len() > RECENT_DEPOSITS {
+            state.deposits.pop_front();
+        }
+        transaction(TransactionType::Deposit, client, tx, Some(amount))
+    }
+
+    fn withdrawal(&mut self) -> Option<Transaction> {
+        let client = self.random_unlocked_client();
+        let available = self.client(client).available;
+        if available <= 0 {
+            return None;
+        }
+        let amount = self.rng.below(available as u64) as i64 + 1;
+        let tx = self.new_tx();
+        self.client(client).available -= amount;
+        Some(transaction(
+            TransactionType::Withdrawal,
+            client,
+            tx,
+            Some(amount),
+        ))
+    }
+
+    fn dispute(&mut self) -> Option<Transaction> {
+        let client = self.random_client();
+        let roll = self.rng.next();
+        let state = self.client(client);
+        if state.deposits.is_empty() {
+            return None;
+        }
+        let index = (roll % state.deposits.len() as u64) as usize;
+        let (tx, amount) = state.deposits.remove(index)?;
+        state.available -= amount;
+        state.disputed.push((tx, amount));
+        Some(transaction(TransactionType::Dispute, client, tx, None))
+    }
+
+    fn settle(&mut self, r#type: TransactionType) -> Option<Transaction> {
+        let client = self.random_client();
+        let roll = self.rng.next();
+        // Locking the last unlocked client would leave nobody to deposit to.
+        let locks = r#type == TransactionType::Chargeback && !self.client(client).locked;
+        if locks && self.unlocked.len() == 1 {
+            return None;
+        }
+        let state = self.client(client);
+        if state.disputed.is_empty() {
+            return None;
+        }
+        let index = (roll % state.disputed.len() as u64) as usize;
+        let (tx, amount) = state.disputed.swap_remove(index);
+        if r#type == TransactionType::Resolve {
+            state.available += amount;
+        } else if locks {
+            self.lock(client);
+        }
+        Some(transaction(r#type, client, tx, None))
+    }
+
+    fn lock(&mut self, client: u16) {
+        self.client(client).locked = true;
+        let Some(position) = self.unlocked_position[usize::from(client - 1)].take() else {
+            return;
+        };
+        self.unlocked.swap_remove(position);
+        if let Some(&moved) = self.unlocked.get(position) {
+            self.unlocked_position[usize::from(moved - 1)] = Some(position);
+        }
+    }
+
+    fn fault(&mut self) -> Transaction {
+        let fault = Fault::ALL[self.rng.below(Fault::ALL.len() as u64) as usize];
+        let generated = match fault {
+            Fault::UnknownTx => None,
+            Fault::WrongClient => self.wrong_client(),
+            Fault::Duplicate => self.duplicate(),
+            Fault::LockedAccount => self.locked_deposit(),
+        };
+        // Faults that need earlier rows fall back to an unknown tx.
+        let (fault, transaction) = generated.unwrap_or_else(|| {
+            let r#type = [
+                TransactionType::Dispute,
+                TransactionType::Resolve,
+                TransactionType::Chargeback,
+            ][self.rng.below(3) as usize];
+            let client = self.random_client();
+            let tx = self.unrecorded_tx();
+            (Fault::UnknownTx, transaction(r#type, client, tx, None))
+        });
+        self.faults[fault as usize] += 1;
+        transaction
+    }
+
+    fn wrong_client(&mut self) -> Option<(Fault, Transaction)> {
+        if self.clients.len() < 2 {
+            return None;
+        }
+        let owner = self.random_client();
+        let roll = self.rng.next();
+        let deposits = &self.client(owner).deposits;
+        let (tx, _) = *deposits.get((roll % deposits.len().max(1) as u64) as usize)?;
+        let offset = self.rng.below(self.clients.len() as u64 - 1) + 1;
+        let client = ((u64::from(owner) - 1 + offset) % self.clients.len() as u64) as u16 + 1;
+        Some((
+            Fault::WrongClient,
+            transaction(TransactionType::Dispute, client, tx, None),
+        ))
+    }
+
+    fn duplicate(&mut self) -> Option<(Fault, Transaction)> {
+        if self.next_tx == 1 {
+            return None;
+        }
+        let tx = self.rng.below(u64::from(self.next_tx - 1)) as u32 + 1;
+        let client = self.random_client();
+        let amount = self.rng.below(MAX_DEPOSIT as u64) as i64 + 1;
+        Some((
+            Fault::Duplicate,
+            transaction(TransactionType::Deposit, client, tx, Some(amount)),
+        ))
+    }
+
+    fn locked_deposit(&mut self) -> Option<(Fault, Transaction)> {
+        let client = self.random_client();
+        if !self.client(client).locked {
+            return None;
+        }
+        let amount = self.rng.below(MAX_DEPOSIT as u64) as i64 + 1;
+        let tx = self.unrecorded_tx();
+        Some((
+            Fault::LockedAccount,
+            transaction(TransactionType::Deposit, client, tx, Some(amount)),
+        ))
+    }
+}
+
+impl Iterator for Workload {
+    type Item = Transaction;
+
+    fn next(&mut self) -> Option<Transaction> {
+        if self.generated == self.config.transactions {
+            return None;
+        }
+        self.generated += 1;
+
+        // 53 random bits give a uniform float in [0, 1).
+        let roll = (self.rng.next() >> 11) as f64 / (1u64 << 53) as f64;
+        if roll < self.config.fault_rate {
+            return Some(self.fault());
+        }
+        let generated = match self.config.mix.pick(self.rng.next()) {
+            TransactionType::Deposit => None,
+            TransactionType::Withdrawal => self.withdrawal(),
+            TransactionType::Dispute => self.dispute(),
+            r#type => self.settle(r#type),
+        };
+        Some(generated.unwrap_or_else(|| self.deposit()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.config.transactions - self.generated) as usize;
+        (remaining, Some(remaining))
+    }
+}
+
+fn transaction(r#type: TransactionType, client: u16, tx: u32, amount: Option<i64>) -> Transaction {
+    Transaction {
+        r#type,
+        client,
+        tx,
+        amount: amount.map(|amount| Decimal::new(amount, AMOUNT_SCALE)),
+    }
+}
+
+// SplitMix64, small and fast with good statistical quality for test data.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number below `n`, which must not be zero. The modulo bias is negligible for
+    /// the small ranges used here.
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Engine;
+
+    fn run(config: WorkloadConfig) -> (Workload, Engine) {
+        let mut workload = Workload::new(config);
+        let mut engine = Engine::new();
+        for transaction in workload.by_ref() {
+            let _ = engine.apply_transaction(transaction);
+        }
+        (workload, engine)
+    }
+
+    #[test]
+    fn test_clean_workload_is_accepted() {
+        let (_, engine) = run(WorkloadConfig {
+            transactions: 20_000,
+            clients: 20,
+            fault_rate: 0.0,
+            ..WorkloadConfig::default()
+        });
+        assert_eq!(engine.metrics.rejected_total(), 0);
+        assert!(engine.metrics.processed(TransactionType::Chargeback) > 0);
+        assert!(engine.metrics.processed(TransactionType::Resolve) > 0);
+        assert!(engine.metrics.locked_accounts() > 0);
+        assert!(engine.metrics.locked_accounts() < 20);
+    }
+
+    #[test]
+    fn test_faults_are_rejected() {
+        let (workload, engine) = run(WorkloadConfig {
+            transactions: 20_000,
+            clients: 10,
+            fault_rate: 0.05,
+            ..WorkloadConfig::default()
+        });
+        for fault in Fault::ALL {
+            assert!(workload.faults(fault) > 0, "no {fault:?}");
+            assert_eq!(
+                engine.metrics.rejected(fault.code()),
+                workload.faults(fault)
+            );
+        }
+        let faults: u64 = Fault::ALL.iter().map(|f| workload.faults(*f)).sum();
+        assert_eq!(engine.metrics.rejected_total(), faults);
+    }
+
+    #[test]
+    fn test_reproducible_from_seed() {
+        let config = WorkloadConfig::default();
+        let rows = |config: WorkloadConfig| {
+            Workload::new(config)
+                .map(|t| (t.r#type, t.client, t.tx, t.amount))
+                .collect::<Vec<_>>()
+        };
+        let first = rows(config.clone());
+        assert_eq!(first.len(), 1000);
+        assert_eq!(first, rows(config.clone()));
+        assert_ne!(first, rows(WorkloadConfig { seed: 7, ..config }));
+    }
+
+    #[test]
+    fn test_parse_mix() {
+        let mix: Mix = "deposit=3, withdrawal=1".parse().unwrap();
+        assert_eq!((mix.deposit, mix.withdrawal, mix.dispute), (3, 1, 0));
+        assert!(matches!(
+            "refund=1".parse::<Mix>(),
+            Err(MixError::UnknownType(_))
+        ));
+        assert!(matches!(
+            "deposit".parse::<Mix>(),
+            Err(MixError::Invalid(_))
+        ));
+        assert!(matches!("deposit=0".parse::<Mix>(), Err(MixError::Empty)));
+    }
+}
@@ -0,0 +1,333 @@
+//! Per-client statements built from the engine history.
+//!
+//! A statement lists every transaction applied for a client, in order, with the dispute
+//! state of the referenced transaction and the running balances after each line. It
+//! requires an engine created with `Engine::with_history`.
+
+use std::io::{self, Write};
+use std::ops::{Bound, RangeBounds};
+use std::str::FromStr;
+
+use rust_decimal::Decimal;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::account::Account;
+use crate::engine::Engine;
+use crate::transaction::{DisputeState, TransactionType};
+
+/// Balances of an account at one point of its history.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Balances {
+    pub available: Decimal,
+    pub held: Decimal,
+    pub total: Decimal,
+    pub locked: bool,
+}
+
+impl From<&Account> for Balances {
+    fn from(account: &Account) -> Self {
+        Self {
+            available: account.get_available(),
+            held: account.held(),
+            total: account.total(),
+            locked: account.is_locked,
+        }
+    }
+}
+
+/// StatementLine is one applied transaction and the balances right after it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatementLine {
+    /// Position of the transaction in the engine history, starting at 1.
+    pub seq: usize,
+    pub tx: u32,
+    pub r#type: TransactionType,
+    /// The deposited or withdrawn amount, or the disputed amount for disputes,
+    /// resolves and chargebacks.
+    pub amount: Decimal,
+    /// Dispute state of transaction `tx` after this line.
+    pub dispute_state: DisputeState,
+    pub balances: Balances,
+}
+
+/// ClientStatement is the history of one client over a range of the engine history.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ClientStatement {
+    pub client: u16,
+    /// Balances before the first line.
+    pub opening: Balances,
+    pub lines: Vec<StatementLine>,
+    /// Balances after the last line.
+    pub closing: Balances,
+}
+
+/// HistoryError represents a statement that cannot be produced.
+#[derive(Debug, Error)]
+pub enum HistoryError {
+    #[error("The engine does not keep a transaction history.")]
+    Disabled,
+    #[error("Client {0} not found.")]
+    ClientNotFound(u16),
+}
+
+/// Build the statement of `client` for the transactions whose `seq` is in `range`.
+/// Balances are replayed from the start of the history, so the opening balance of a
+/// partial range is exact.
+pub fn client_statement(
+    engine: &Engine,
+    client: u16,
+    range: impl RangeBounds<usize>,
+) -> Result<ClientStatement, HistoryError> {
+    let history = engine.history.as_ref().ok_or(HistoryError::Disabled)?;
+    if !engine.accounts.contains_key(&client) {
+        return Err(HistoryError::ClientNotFound(client));
+    }
+
+    // Transactions of one client only touch that client's account, so replaying them
+    // alone reproduces its balances after every step.
+    let mut replay = Engine::new();
+    let mut opening = Balances::default();
+    let mut lines = Vec::new();
+    for (index, transaction) in history.iter().enumerate() {
+        let seq = index + 1;
+        if transaction.client != client {
+            continue;
+        }
+        let past_end = match range.end_bound() {
+            Bound::Included(end) => seq > *end,
+            Bound::Excluded(end) => seq >= *end,
+            Bound::Unbounded => false,
+        };
+        if past_end {
+            break;
+        }
+        let tx = transaction.tx;
+        let r#type = transaction.r#type;
+        if replay.apply_transaction(transaction.clone()).is_err() {
+            // Only applied transactions are recorded, so this cannot happen.
+            continue;
+        }
+
+        let record = &replay.transactions[&tx];
+        let balances = Balances::from(&replay.accounts[&client]);
+        if !range.contains(&seq) {
+            opening = balances;
+            continue;
+        }
+        lines.push(StatementLine {
+            seq,
+            tx,
+            r#type,
+            amount: record.transaction.amount.unwrap_or_default(),
+            dispute_state: record.dispute_state,
+            balances,
+        });
+    }
+
+    let closing = lines
+        .last()
+        .map_or_else(|| opening.clone(), |line| line.balances.clone());
+    Ok(ClientStatement {
+        client,
+        opening,
+        lines,
+        closing,
+    })
+}
+
+/// OutputFormat is the format a `ClientStatement` is written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Csv,
+    Json,
+    Text,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(OutputFormat::Csv),
+            "json" => Ok(OutputFormat::Json),
+            "text" | "txt" => Ok(OutputFormat::Text),
+            _ => Err(format!(
+                "Unknown statement format '{s}'. Expected csv, json or text."
+            )),
+        }
+    }
+}
+
+impl ClientStatement {
+    pub fn write<W: Write + ?Sized>(&self, format: OutputFormat, writer: &mut W) -> io::Result<()> {
+        match format {
+            OutputFormat::Csv => self.write_csv(writer),
+            OutputFormat::Json => {
+                serde_json::to_writer_pretty(&mut *writer, self)?;
+                writeln!(writer)
+            }
+            OutputFormat::Text => self.write_text(writer),
+        }
+    }
+
+    /// One row per line, without the opening balance.
+    fn write_csv<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(
+            writer,
+            "seq, tx, type, amount, dispute_state, available, held, total, locked"
+        )?;
+        for line in &self.lines {
+            writeln!(
+                writer,
+                "{}, {}, {}, {}, {}, {}, {}, {}, {}",
+                line.seq,
+                line.tx,
+                line.r#type.name(),
+                line.amount,
+                line.dispute_state.name(),
+                line.balances.available,
+                line.balances.held,
+                line.balances.total,
+                line.balances.locked
+            )?;
+        }
+        Ok(())
+    }
+
+    fn write_text<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "Statement for client {}", self.client)?;
+        writeln!(writer)?;
+        writeln!(
+            writer,
+            "{:>8} {:>10}  {:<10} {:>14}  {:<12} {:>14} {:>14} {:>14}",
+            "seq", "tx", "type", "amount", "dispute", "available", "held", "total"
+        )?;
+        let balance_row = |writer: &mut W, label: &str, balances: &Balances| {
+            writeln!(
+                writer,
+                "{:<60} {:>14} {:>14} {:>14}",
+                label,
+                balances.available.to_string(),
+                balances.held.to_string(),
+                balances.total.to_string()
+            )
+        };
+        balance_row(writer, "Opening balance", &self.opening)?;
+        for line in &self.lines {
+            writeln!(
+                writer,
+                "{:>8} {:>10}  {:<10} {:>14}  {:<12} {:>14} {:>14} {:>14}",
+                line.seq,
+                line.tx,
+                line.r#type.name(),
+                line.amount.to_string(),
+                line.dispute_state.name(),
+                line.balances.available.to_string(),
+                line.balances.held.to_string(),
+                line.balances.total.to_string()
+            )?;
+        }
+        balance_row(writer, "Closing balance", &self.closing)?;
+        if self.closing.locked {
+            writeln!(writer, "The account is locked.")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{apply_all, tx};
+
+    fn engine() -> Engine {
+        use TransactionType::*;
+        apply_all(
+            Engine::new().with_history(),
+            [
+                tx(Deposit, 1, 1, Some(1000)),
+                tx(Deposit, 2, 2, Some(500)),
+                tx(Withdrawal, 1, 3, Some(250)),
+                tx(Dispute, 1, 1, None),
+                // Rejected, so it is not part of the history.
+                tx(Withdrawal, 1, 4, Some(1000)),
+                tx(Chargeback, 1, 1, None),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_statement_with_running_balances() {
+        let statement = client_statement(&engine(), 1, ..).unwrap();
+        let summary: Vec<_> = statement
+            .lines
+            .iter()
+            .map(|line| {
+                (
+                    line.seq,
+                    line.tx,
+                    line.dispute_state,
+                    line.balances.available,
+                    line.balances.held,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    1,
+                    1,
+                    DisputeState::None,
+                    Decimal::new(100, 1),
+                    Decimal::ZERO
+                ),
+                (3, 3, DisputeState::None, Decimal::new(75, 1), Decimal::ZERO),
+                (
+                    4,
+                    1,
+                    DisputeState::Disputed,
+                    Decimal::new(-25, 1),
+                    Decimal::new(100, 1)
+                ),
+                (
+                    5,
+                    1,
+                    DisputeState::ChargedBack,
+                    Decimal::new(-25, 1),
+                    Decimal::ZERO
+                ),
+            ]
+        );
+        assert_eq!(statement.opening, Balances::default());
+        assert!(statement.closing.locked);
+    }
+
+    #[test]
+    fn test_statement_range_and_csv() {
+        let statement = client_statement(&engine(), 1, 3..=4).unwrap();
+        assert_eq!(statement.opening.total, Decimal::new(100, 1));
+
+        let mut output = Vec::new();
+        statement.write(OutputFormat::Csv, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "seq, tx, type, amount, dispute_state, available, held, total, locked\n\
+             3, 3, withdrawal, 2.50, none, 7.50, 0, 7.50, false\n\
+             4, 1, dispute, 10.00, disputed, -2.50, 10.00, 7.50, false\n"
+        );
+    }
+
+    #[test]
+    fn test_statement_errors() {
+        assert!(matches!(
+            client_statement(&Engine::new(), 1, ..),
+            Err(HistoryError::Disabled)
+        ));
+        assert!(matches!(
+            client_statement(&engine(), 9, ..),
+            Err(HistoryError::ClientNotFound(9))
+        ));
+    }
+}
@@ -0,0 +1,320 @@
+//! HTTP/JSON API over a shared `Engine`.
+//!
+//! - `POST /transactions` applies one transaction object or an array of them.
+//! - `GET /accounts` lists all accounts in report order.
+//! - `GET /accounts/{client}` returns one account.
+//! - `GET /transactions/{tx}` returns a recorded transaction with its `DisputeState`.
+//! - `GET /metrics` returns the engine metrics in the Prometheus text format.
+//!
+//! Errors are returned as `{"code": "...", "message": "..."}` with a matching status.
+
+use axum::extract::rejection::{JsonRejection, PathRejection};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use tokio::io;
+use tokio::net::TcpListener;
+
+use crate::account::AccountSummary;
+use crate::engine::EngineError;
+use crate::metrics;
+use crate::server::SharedEngine;
+use crate::transaction::Transaction;
+
+/// Build the API router backed by `engine`.
+pub fn router(engine: SharedEngine) -> Router {
+    Router::new()
+        .route("/transactions", post(post_transactions))
+        .route("/transactions/{tx}", get(get_transaction))
+        .route("/accounts", get(get_accounts))
+        .route("/accounts/{client}", get(get_account))
+        .route("/metrics", get(metrics::get_metrics))
+        .with_state(engine)
+}
+
+/// Serve the API on `listener` until the task is cancelled.
+pub async fn serve(listener: TcpListener, engine: SharedEngine) -> io::Result<()> {
+    log::info!("HTTP API listening on {}", listener.local_addr()?);
+    axum::serve(listener, router(engine)).await
+}
+
+/// ErrorBody is the JSON shape of every error the API returns.
+#[derive(Debug, Serialize)]
+pub struct ErrorBody {
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl ErrorBody {
+    fn new(code: &'static str, message: impl ToString) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<&EngineError> for ErrorBody {
+    fn from(error: &EngineError) -> Self {
+        Self::new(error.code(), error)
+    }
+}
+
+struct ApiError(StatusCode, ErrorBody);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(self.1)).into_response()
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        ApiError(
+            rejection.status(),
+            ErrorBody::new("invalid_request", rejection.body_text()),
+        )
+    }
+}
+
+// A path id that is not a number in range is a bad request.
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        ApiError(
+            StatusCode::BAD_REQUEST,
+            ErrorBody::new("invalid_request", rejection.body_text()),
+        )
+    }
+}
+
+/// TransactionBatch accepts either a single transaction or an array of them.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum TransactionBatch {
+    One(Transaction),
+    Many(Vec<Transaction>),
+}
+
+/// TransactionResult reports the outcome of one posted transaction.
+#[derive(Debug, Serialize)]
+pub struct TransactionResult {
+    pub tx: u32,
+    pub status: TransactionStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorBody>,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionStatus {
+    Applied,
+    Rejected,
+}
+
+fn apply(engine: &SharedEngine, transaction: Transaction) -> TransactionResult {
+    let tx = transaction.tx;
+    match engine
+        .lock()
+        .expect("Engine lock poisoned")
+        .apply_transaction(transaction)
+    {
+        Ok(()) => TransactionResult {
+            tx,
+            status: TransactionStatus::Applied,
+            error: None,
+        },
+        Err(e) => TransactionResult {
+            tx,
+            status: TransactionStatus::Rejected,
+            error: Some(ErrorBody::from(&e)),
+        },
+    }
+}
+
+// A single transaction gets 200 or 422 depending on the outcome. A batch always gets
+// 200 with one result per transaction, in order.
+async fn post_transactions(
+    State(engine): State<SharedEngine>,
+    body: Result<Json<TransactionBatch>, JsonRejection>,
+) -> Result<Response, ApiError> {
+    let Json(batch) = body?;
+    let response = match batch {
+        TransactionBatch::One(transaction) => {
+            let result = apply(&engine, transaction);
+            let status = match result.status {
+                TransactionStatus::Applied => StatusCode::OK,
+                TransactionStatus::Rejected => StatusCode::UNPROCESSABLE_ENTITY,
+            };
+            (status, Json(result)).into_response()
+        }
+        TransactionBatch::Many(transactions) => {
+            let results: Vec<TransactionResult> = transactions
+                .into_iter()
+                .map(|transaction| apply(&engine, transaction))
+                .collect();
+            Json(results).into_response()
+        }
+    };
+    Ok(response)
+}
+
+async fn get_accounts(State(engine): State<SharedEngine>) -> Json<Vec<AccountSummary>> {
+    Json(
+        engine
+            .lock()
+            .expect("Engine lock poisoned")
+            .account_summaries(),
+    )
+}
+
+async fn get_account(
+    State(engine): State<SharedEngine>,
+    client: Result<Path<u16>, PathRejection>,
+) -> Result<Json<AccountSummary>, ApiError> {
+    let Path(client) = client?;
+    let engine = engine.lock().expect("Engine lock poisoned");
+    engine
+        .accounts
+        .get(&client)
+        .map(|account| Json(AccountSummary::from(account)))
+        .ok_or_else(|| {
+            ApiError(
+                StatusCode::NOT_FOUND,
+                ErrorBody::new("account_not_found", format!("Client {client} not found.")),
+            )
+        })
+}
+
+async fn get_transaction(
+    State(engine): State<SharedEngine>,
+    tx: Result<Path<u32>, PathRejection>,
+) -> Result<Response, ApiError> {
+    let Path(tx) = tx?;
+    let engine = engine.lock().expect("Engine lock poisoned");
+    let record = engine.transactions.get(&tx).ok_or_else(|| {
+        ApiError(
+            StatusCode::NOT_FOUND,
+            ErrorBody::new(
+                "transaction_not_found",
+                format!("Transaction {tx} not found."),
+            ),
+        )
+    })?;
+    Ok(Json(record).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{Body, to_bytes};
+    use axum::http::Request;
+    use serde_json::{Value, json};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    async fn call(
+        engine: &SharedEngine,
+        method: &str,
+        uri: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))
+            .unwrap();
+        let response = router(Arc::clone(engine)).oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_post_and_query() {
+        let engine = SharedEngine::default();
+
+        let (status, body) = call(
+            &engine,
+            "POST",
+            "/transactions",
+            Some(json!({"type": "deposit", "client": 1, "tx": 1, "amount": "10.5"})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({"tx": 1, "status": "applied"}));
+
+        let (status, body) = call(
+            &engine,
+            "POST",
+            "/transactions",
+            Some(json!([
+                {"type": "withdrawal", "client": 1, "tx": 2, "amount": 100},
+                {"type": "dispute", "client": 1, "tx": 1},
+            ])),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body[0]["status"], "rejected");
+        assert_eq!(body[0]["error"]["code"], "insufficient_funds");
+        assert_eq!(body[1], json!({"tx": 1, "status": "applied"}));
+
+        let (status, body) = call(&engine, "GET", "/accounts/1", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            json!({"client": 1, "available": "0.0", "held": "10.5", "total": "10.5", "locked": false})
+        );
+
+        let (_, body) = call(&engine, "GET", "/accounts", None).await;
+        assert_eq!(body.as_array().unwrap().len(), 1);
+
+        let (status, body) = call(&engine, "GET", "/transactions/1", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["dispute_state"], "disputed");
+        assert_eq!(body["transaction"]["client"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_errors_are_structured() {
+        let engine = SharedEngine::default();
+
+        let (status, body) = call(
+            &engine,
+            "POST",
+            "/transactions",
+            Some(json!({"type": "resolve", "client": 1, "tx": 7})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["status"], "rejected");
+        assert_eq!(body["error"]["code"], "transaction_not_found");
+
+        let (status, body) = call(&engine, "GET", "/accounts/9", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], "account_not_found");
+
+        let (status, body) = call(&engine, "GET", "/transactions/9", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], "transaction_not_found");
+
+        for uri in ["/accounts/x", "/accounts/70000", "/transactions/-1"] {
+            let (status, body) = call(&engine, "GET", uri, None).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{uri}");
+            assert_eq!(body["code"], "invalid_request", "{uri}");
+        }
+
+        let (status, body) = call(
+            &engine,
+            "POST",
+            "/transactions",
+            Some(json!({"type": "gift"})),
+        )
+        .await;
+        assert!(status.is_client_error());
+        assert_eq!(body["code"], "invalid_request");
+    }
+}
@@ -0,0 +1,167 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Cursor, Read};
+use std::path::{Path, PathBuf};
+
+use flate2::read::MultiGzDecoder;
+
+/// Path that selects standard input instead of a file.
+pub const STDIN_PATH: &str = "-";
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Input is where a transaction source reads from: a path (or `-` for stdin) that is
+/// opened when transactions are requested, or an already open reader.
+pub enum Input {
+    Path(String),
+    Reader(Box<dyn Read>),
+}
+
+impl Input {
+    /// Open the input, decompressing it if needed.
+    pub fn open(self) -> io::Result<Box<dyn BufRead>> {
+        match self {
+            Input::Path(path) => open(&path),
+            Input::Reader(reader) => decompress(reader),
+        }
+    }
+}
+
+/// Open `path` for reading, or standard input for `-`, decompressing it if needed.
+pub fn open(path: &str) -> io::Result<Box<dyn BufRead>> {
+    if path == STDIN_PATH {
+        decompress(io::stdin())
+    } else {
+        decompress(File::open(path)?)
+    }
+}
+
+/// Expand a leading `~` to the home directory and return the canonical form of `path`.
+/// Paths that do not exist yet, such as output files, are canonicalized through their
+/// parent directory. `-` and glob patterns are returned with only `~` expanded.
+pub fn normalize_path(path: &str) -> io::Result<String> {
+    if path == STDIN_PATH {
+        return Ok(path.to_string());
+    }
+    let expanded = expand_home(path)?;
+    if expanded.to_string_lossy().contains(['*', '?', '[']) {
+        return Ok(expanded.to_string_lossy().into_owned());
+    }
+    let canonical = match expanded.canonicalize() {
+        Ok(canonical) => canonical,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let (Some(parent), Some(name)) = (expanded.parent(), expanded.file_name()) else {
+                return Err(e);
+            };
+            let parent = if parent.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                parent
+            };
+            parent.canonicalize()?.join(name)
+        }
+        Err(e) => return Err(e),
+    };
+    Ok(canonical.to_string_lossy().into_owned())
+}
+
+fn expand_home(path: &str) -> io::Result<PathBuf> {
+    let rest = match path.strip_prefix('~') {
+        Some("") => "",
+        Some(rest) if rest.starts_with(['/', std::path::MAIN_SEPARATOR]) => &rest[1..],
+        _ => return Ok(PathBuf::from(path)),
+    };
+    let home = std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Home directory not set"))?;
+    Ok(PathBuf::from(home).join(rest))
+}
+
+/// Wrap `reader` so gzip and zstd input, detected by magic bytes, is decompressed
+/// while streaming. Anything else is passed through unchanged.
+pub fn decompress<R: Read + 'static>(mut reader: R) -> io::Result<Box<dyn BufRead>> {
+    // Peek at the first bytes and put them back in front of the stream afterwards.
+    let mut magic = [0u8; 4];
+    let mut len = 0;
+    while len < magic.len() {
+        match reader.read(&mut magic[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    let head = &magic[..len];
+    let stream = Cursor::new(head.to_vec()).chain(reader);
+
+    Ok(if head.starts_with(GZIP_MAGIC) {
+        Box::new(BufReader::new(MultiGzDecoder::new(stream)))
+    } else if head.starts_with(ZSTD_MAGIC) {
+        Box::new(BufReader::new(zstd::Decoder::new(stream)?))
+    } else {
+        Box::new(BufReader::new(stream))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{Compression, write::GzEncoder};
+    use std::io::Write;
+
+    const CSV: &str = "type, client, tx, amount\ndeposit, 1, 1, 1.0\n";
+
+    fn read_all(reader: Box<dyn BufRead>) -> String {
+        let mut output = String::new();
+        let mut reader = reader;
+        reader.read_to_string(&mut output).unwrap();
+        output
+    }
+
+    #[test]
+    fn test_plain_passthrough() {
+        assert_eq!(read_all(decompress(CSV.as_bytes()).unwrap()), CSV);
+        assert_eq!(read_all(decompress(&b"ab"[..]).unwrap()), "ab");
+        assert_eq!(read_all(decompress(&b""[..]).unwrap()), "");
+    }
+
+    #[test]
+    fn test_gzip_detected() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(CSV.as_bytes()).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        assert_eq!(read_all(decompress(Cursor::new(compressed)).unwrap()), CSV);
+    }
+
+    #[test]
+    fn test_zstd_detected() {
+        let compressed = zstd::encode_all(CSV.as_bytes(), 0).unwrap();
+        assert_eq!(read_all(decompress(Cursor::new(compressed)).unwrap()), CSV);
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        let home = PathBuf::from(std::env::var_os("HOME").unwrap());
+        assert_eq!(expand_home("~").unwrap(), home);
+        assert_eq!(expand_home("~/in.csv").unwrap(), home.join("in.csv"));
+        assert_eq!(
+            expand_home("~other/in.csv").unwrap(),
+            PathBuf::from("~other/in.csv")
+        );
+
+        let cwd = std::env::current_dir().unwrap().canonicalize().unwrap();
+        assert_eq!(
+            normalize_path("tests/../tests/sample.csv").unwrap(),
+            cwd.join("tests/sample.csv").to_string_lossy()
+        );
+        // Output files that do not exist yet keep their name in a canonical directory.
+        assert_eq!(
+            normalize_path("./tests/new.csv").unwrap(),
+            cwd.join("tests/new.csv").to_string_lossy()
+        );
+        assert_eq!(normalize_path("-").unwrap(), "-");
+        assert_eq!(normalize_path("inbox/*.csv").unwrap(), "inbox/*.csv");
+        assert!(normalize_path("missing/dir/new.csv").is_err());
+    }
+}
@@ -0,0 +1,291 @@
+//! Double-entry ledger behind the account balances.
+//!
+//! Every engine operation posts a balanced journal entry moving an amount from one
+//! ledger account to another. Balances are signed so that they always sum to zero:
+//! client accounts are liabilities and hold positive balances equal to the funds owed
+//! to the client, while settlement clearing, the money received from and paid out to
+//! the outside world, holds the matching negative balance. Funds clawed back by
+//! chargebacks are written off to chargeback loss rather than paid out.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::transaction::TransactionType;
+
+/// LedgerAccount is an account of the ledger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LedgerAccount {
+    /// Funds received from or paid out to payment providers and banks.
+    SettlementClearing,
+    /// Funds written off by chargebacks.
+    ChargebackLoss,
+    /// Funds a client can withdraw.
+    ClientAvailable(u16),
+    /// Funds of a client held by open disputes.
+    ClientHeld(u16),
+}
+
+impl fmt::Display for LedgerAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LedgerAccount::SettlementClearing => write!(f, "settlement_clearing"),
+            LedgerAccount::ChargebackLoss => write!(f, "chargeback_loss"),
+            LedgerAccount::ClientAvailable(client) => write!(f, "client:{client}:available"),
+            LedgerAccount::ClientHeld(client) => write!(f, "client:{client}:held"),
+        }
+    }
+}
+
+/// EntryReference is what a journal entry was posted for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "reference", rename_all = "snake_case")]
+pub enum EntryReference {
+    /// An operation of transaction `tx`.
+    Transaction { tx: u32, r#type: TransactionType },
+    /// The balance of an account when a saved state was restored, which belongs to no
+    /// transaction.
+    OpeningBalance,
+}
+
+/// JournalEntry moves `amount` from the `from` account to the `to` account.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct JournalEntry {
+    pub reference: EntryReference,
+    pub from: LedgerAccount,
+    pub to: LedgerAccount,
+    pub amount: Decimal,
+}
+
+/// Ledger keeps the balance of every ledger account, and the journal of postings when
+/// enabled with `with_journal`. The balances are updated by every posting and are the
+/// source of truth, the journal only records how they came about.
+#[derive(Debug, Default)]
+pub struct Ledger {
+    journal: Option<Vec<JournalEntry>>,
+    balances: HashMap<LedgerAccount, Decimal>,
+}
+
+impl Ledger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep the posted entries, e.g. to undo them. Long-running engines leave this off
+    /// so that memory does not grow with every transaction.
+    pub fn with_journal(mut self) -> Self {
+        self.journal = Some(Vec::new());
+        self
+    }
+
+    /// Post a transfer of `amount` from `from` to `to` for transaction `tx`.
+    pub fn post(
+        &mut self,
+        tx: u32,
+        r#type: TransactionType,
+        from: LedgerAccount,
+        to: LedgerAccount,
+        amount: Decimal,
+    ) {
+        self.push(EntryReference::Transaction { tx, r#type }, from, to, amount);
+    }
+
+    /// Post the opening balance of `account`, moved in from settlement clearing.
+    pub fn open(&mut self, account: LedgerAccount, amount: Decimal) {
+        self.push(
+            EntryReference::OpeningBalance,
+            LedgerAccount::SettlementClearing,
+            account,
+            amount,
+        );
+    }
+
+    /// Remove the entries posted after the first `len` and revert their balances. Does
+    /// nothing without a journal.
+    pub fn truncate(&mut self, len: usize) {
+        let Some(journal) = &mut self.journal else {
+            return;
+        };
+        let len = len.min(journal.len());
+        for entry in journal.drain(len..) {
+            *self.balances.entry(entry.from).or_default() += entry.amount;
+            *self.balances.entry(entry.to).or_default() -= entry.amount;
+        }
+    }
+
+    fn push(
+        &mut self,
+        reference: EntryReference,
+        from: LedgerAccount,
+        to: LedgerAccount,
+        amount: Decimal,
+    ) {
+        *self.balances.entry(from).or_default() -= amount;
+        *self.balances.entry(to).or_default() += amount;
+        if let Some(journal) = &mut self.journal {
+            journal.push(JournalEntry {
+                reference,
+                from,
+                to,
+                amount,
+            });
+        }
+    }
+
+    /// The balance of `account`, zero if nothing was posted to it.
+    pub fn balance(&self, account: LedgerAccount) -> Decimal {
+        self.balances.get(&account).copied().unwrap_or_default()
+    }
+
+    /// All journal entries in the order they were posted, empty without a journal.
+    pub fn journal(&self) -> &[JournalEntry] {
+        self.journal.as_deref().unwrap_or_default()
+    }
+
+    /// Add the journal and balances of `other`, e.g. a ledger kept by another shard.
+    pub fn merge(&mut self, other: Ledger) {
+        for (account, balance) in other.balances {
+            *self.balances.entry(account).or_default() += balance;
+        }
+        if let (Some(journal), Some(other)) = (&mut self.journal, other.journal) {
+            journal.extend(other);
+        }
+    }
+
+    /// The balance of every ledger account, ordered by account.
+    pub fn trial_balance(&self) -> TrialBalance {
+        let mut rows: Vec<(LedgerAccount, Decimal)> = self
+            .balances
+            .iter()
+            .map(|(account, balance)| (*account, *balance))
+            .collect();
+        rows.sort_by_key(|(account, _)| *account);
+        TrialBalance { rows }
+    }
+}
+
+/// TrialBalance lists the balance of every ledger account. Its total is always zero.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrialBalance {
+    pub rows: Vec<(LedgerAccount, Decimal)>,
+}
+
+impl TrialBalance {
+    /// Sum of all balances, zero for a consistent ledger.
+    pub fn total(&self) -> Decimal {
+        self.rows.iter().map(|(_, balance)| balance).sum()
+    }
+
+    /// Write the trial balance as CSV, followed by a total row.
+    pub fn write<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "account, balance")?;
+        for (account, balance) in &self.rows {
+            writeln!(writer, "{account}, {balance}")?;
+        }
+        writeln!(writer, "total, {}", self.total())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_postings_balance() {
+        let mut ledger = Ledger::new().with_journal();
+        let amount = Decimal::new(150, 2);
+        ledger.post(
+            1,
+            TransactionType::Deposit,
+            LedgerAccount::SettlementClearing,
+            LedgerAccount::ClientAvailable(1),
+            amount,
+        );
+        ledger.post(
+            1,
+            TransactionType::Dispute,
+            LedgerAccount::ClientAvailable(1),
+            LedgerAccount::ClientHeld(1),
+            amount,
+        );
+
+        assert_eq!(
+            ledger.balance(LedgerAccount::ClientAvailable(1)),
+            Decimal::ZERO
+        );
+        assert_eq!(ledger.balance(LedgerAccount::ClientHeld(1)), amount);
+        assert_eq!(ledger.balance(LedgerAccount::SettlementClearing), -amount);
+        assert_eq!(ledger.journal().len(), 2);
+        assert_eq!(
+            ledger.journal()[1].reference,
+            EntryReference::Transaction {
+                tx: 1,
+                r#type: TransactionType::Dispute
+            }
+        );
+
+        let trial_balance = ledger.trial_balance();
+        assert_eq!(trial_balance.total(), Decimal::ZERO);
+        let mut output = Vec::new();
+        trial_balance.write(&mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "account, balance\n\
+             settlement_clearing, -1.50\n\
+             client:1:available, 0.00\n\
+             client:1:held, 1.50\n\
+             total, 0.00\n"
+        );
+    }
+
+    #[test]
+    fn test_opening_balance_and_truncate() {
+        let mut ledger = Ledger::new().with_journal();
+        ledger.open(LedgerAccount::ClientAvailable(1), Decimal::new(500, 2));
+        ledger.post(
+            1,
+            TransactionType::Withdrawal,
+            LedgerAccount::ClientAvailable(1),
+            LedgerAccount::SettlementClearing,
+            Decimal::new(200, 2),
+        );
+        assert_eq!(
+            ledger.journal()[0].reference,
+            EntryReference::OpeningBalance
+        );
+        assert_eq!(
+            ledger.balance(LedgerAccount::ClientAvailable(1)),
+            Decimal::new(300, 2)
+        );
+
+        ledger.truncate(1);
+        assert_eq!(ledger.journal().len(), 1);
+        assert_eq!(
+            ledger.balance(LedgerAccount::ClientAvailable(1)),
+            Decimal::new(500, 2)
+        );
+        assert_eq!(ledger.trial_balance().total(), Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_without_journal() {
+        let mut ledger = Ledger::new();
+        ledger.post(
+            1,
+            TransactionType::Deposit,
+            LedgerAccount::SettlementClearing,
+            LedgerAccount::ClientAvailable(1),
+            Decimal::new(150, 2),
+        );
+        assert!(ledger.journal().is_empty());
+        assert_eq!(
+            ledger.balance(LedgerAccount::ClientAvailable(1)),
+            Decimal::new(150, 2)
+        );
+        assert_eq!(ledger.trial_balance().total(), Decimal::ZERO);
+    }
+}
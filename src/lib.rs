@@ -1,6 +1,31 @@
-mod account;
+pub mod account;
+pub mod async_engine;
+pub mod audit;
+pub mod binary;
+pub mod convert;
+pub mod dialect;
+pub mod diff;
 pub mod engine;
+pub mod generate;
+pub mod history;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod input;
+pub mod ledger;
+pub mod metrics;
+pub mod multi_file;
+pub mod reconcile;
+pub mod repl;
+pub mod report;
+pub mod server;
+pub mod sharded;
+pub mod snapshot;
+pub mod statement;
+#[cfg(test)]
+pub(crate) mod test_support;
 pub mod transaction;
+pub mod validate;
+pub mod watch;
 
 use engine::Engine;
 use transaction::TransactionSource;
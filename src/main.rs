@@ -1,26 +1,861 @@
+use std::fmt::Display;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use log::info;
 
 use rust_toy_tx_engine::{
-    engine::Engine, run_engine_with_source, transaction::CsvTransactionSource,
+    audit::JsonLinesAuditSink,
+    binary::BinaryTransactionSource,
+    convert,
+    dialect::CsvDialect,
+    diff,
+    engine::Engine,
+    generate::{Fault, Mix, Workload, WorkloadConfig},
+    history::{self, OutputFormat},
+    input,
+    multi_file::{FileOrder, MultiFileTransactionSource},
+    reconcile,
+    repl::Repl,
+    report::{self, Format},
+    server::{self, SharedEngine},
+    snapshot::Snapshot,
+    statement::{ClientMapping, StatementFormat, StatementTransactionSource},
+    transaction::{CsvTransactionSource, JsonLinesTransactionSource, TransactionSource},
+    validate,
+    watch::{FileTailer, InboxWatcher},
 };
 
+/// Every row was parsed and applied.
+const EXIT_OK: i32 = 0;
+/// Invalid arguments, unreadable input or unwritable output.
+const EXIT_ERROR: i32 = 1;
+/// `reconcile` found differences.
+const EXIT_MISMATCH: i32 = 2;
+/// Some rows could not be parsed and were skipped.
+const EXIT_MALFORMED: i32 = 3;
+/// Every row was parsed, but the engine rejected some of them.
+const EXIT_REJECTED: i32 = 4;
+
+const EXIT_CODES: &str = "Exit codes:
+  0  every row was parsed and applied
+  1  invalid arguments, unreadable input or unwritable output
+  2  reconcile found differences
+  3  some rows could not be parsed and were skipped
+  4  every row was parsed, but the engine rejected some of them";
+
+/// Toy payments engine: applies deposits, withdrawals, disputes, resolves and
+/// chargebacks to client accounts.
+#[derive(Parser)]
+#[command(version, about, after_help = EXIT_CODES)]
+struct Cli {
+    #[command(flatten)]
+    logging: LoggingArgs,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Args)]
+struct LoggingArgs {
+    /// Log level or filter (error, warn, info, debug, trace), overrides RUST_LOG.
+    #[arg(long, global = true)]
+    log_level: Option<String>,
+    /// Log output format.
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+    /// Log everything that happens to transactions of this client.
+    #[arg(long, global = true, value_name = "CLIENT")]
+    trace_client: Option<u16>,
+    /// Log everything that happens to this transaction.
+    #[arg(long, global = true, value_name = "TX")]
+    trace_tx: Option<u32>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum LogFormat {
+    Text,
+    Json,
+}
+
+#[derive(Args)]
+struct InputArgs {
+    /// Input files, `-` for standard input, directories or glob patterns.
+    #[arg(required = true, value_name = "INPUT")]
+    inputs: Vec<String>,
+    /// Input format (csv, jsonl, binary, ofx or camt), detected from the file extension
+    /// by default.
+    #[arg(long)]
+    format: Option<Format>,
+    /// TOML file mapping the accounts of ofx or camt bank statements to clients.
+    #[arg(long, value_name = "FILE")]
+    client_mapping: Option<String>,
+    /// TOML file describing the CSV layout.
+    #[arg(long, value_name = "FILE")]
+    dialect: Option<String>,
+    /// Merge the rows of several CSV files by this column instead of by file name.
+    #[arg(long, value_name = "COLUMN")]
+    order_by: Option<String>,
+}
+
+#[derive(Args)]
+struct OutputArgs {
+    /// Write the output to this file instead of standard output.
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Apply the inputs and write the account report.
+    Process {
+        #[command(flatten)]
+        input: InputArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+        /// Append structured audit events to this JSON Lines file.
+        #[arg(long, value_name = "FILE")]
+        audit: Option<String>,
+        /// Write metrics in the Prometheus text format to this file.
+        #[arg(long, value_name = "FILE")]
+        metrics: Option<String>,
+        /// Save the accounts and transactions as a JSON snapshot.
+        #[arg(long, value_name = "FILE")]
+        snapshot: Option<String>,
+        /// Report format (csv or jsonl), detected from the output file extension by
+        /// default.
+        #[arg(long, value_name = "FORMAT")]
+        output_format: Option<Format>,
+    },
+    /// Dry run the inputs against a scratch engine and report parse errors, rejected
+    /// rows, accounts that would be locked and net movement per client. Nothing is
+    /// written besides the report on standard output.
+    Validate {
+        #[command(flatten)]
+        input: InputArgs,
+    },
+    /// Apply the inputs and write the statement of one client.
+    Replay {
+        #[command(flatten)]
+        input: InputArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+        /// Client whose statement is written.
+        #[arg(long)]
+        client: u16,
+        /// First sequence number, counting applied transactions from 1.
+        #[arg(long, default_value_t = 1)]
+        from: usize,
+        /// Last sequence number.
+        #[arg(long)]
+        to: Option<usize>,
+        /// Statement format (csv, json or text).
+        #[arg(long, default_value = "text")]
+        statement_format: OutputFormat,
+    },
+    /// Write the account report of a snapshot or inputs, or the ledger trial balance.
+    Report {
+        #[command(flatten)]
+        input: InputArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+        /// Write the trial balance of the ledger instead of the accounts.
+        #[arg(long)]
+        trial_balance: bool,
+        /// Report format (csv or jsonl), detected from the output file extension by
+        /// default. The trial balance is always CSV.
+        #[arg(long, value_name = "FORMAT")]
+        output_format: Option<Format>,
+    },
+    /// Translate transactions between formats, detected from the file extensions.
+    Convert { input: String, output: String },
+    /// Write a synthetic workload, in the format of the output file extension or CSV.
+    Generate {
+        #[command(flatten)]
+        output: OutputArgs,
+        /// Number of rows.
+        #[arg(short = 'n', long, default_value_t = 1000)]
+        transactions: u64,
+        /// Number of clients, with ids from 1.
+        #[arg(long, default_value_t = 100)]
+        clients: u16,
+        /// Weights of the transaction types, e.g. deposit=70,withdrawal=25,dispute=5.
+        #[arg(long)]
+        mix: Option<Mix>,
+        /// Share of rows, between 0 and 1, replaced by rows the engine rejects.
+        #[arg(long, default_value_t = 0.01)]
+        fault_rate: f64,
+        /// Seed of the generator, the same seed always gives the same rows.
+        #[arg(long, default_value_t = 42)]
+        seed: u64,
+    },
+    /// Compare the accounts after applying the inputs with expected balances.
+    Reconcile {
+        /// Expected balances in the account report format.
+        expected: String,
+        #[command(flatten)]
+        input: InputArgs,
+    },
+    /// Compare two runs. Each side is an input file or a `.json` snapshot.
+    Diff {
+        left: String,
+        right: String,
+        /// TOML file describing the CSV layout of both inputs.
+        #[arg(long, value_name = "FILE")]
+        dialect: Option<String>,
+        /// TOML file describing the CSV layout of the left input, overrides `--dialect`.
+        #[arg(long, value_name = "FILE")]
+        before_dialect: Option<String>,
+        /// TOML file describing the CSV layout of the right input, overrides `--dialect`.
+        #[arg(long, value_name = "FILE")]
+        after_dialect: Option<String>,
+    },
+    /// Load an input or `.json` snapshot into an interactive shell.
+    Repl {
+        /// Input file or snapshot, the shell starts empty without one.
+        input: Option<String>,
+        /// TOML file describing the CSV layout of the input.
+        #[arg(long, value_name = "FILE")]
+        dialect: Option<String>,
+        /// Run the commands in this file instead of reading them from the terminal.
+        #[arg(long, value_name = "FILE")]
+        script: Option<String>,
+        /// File the command history is kept in.
+        #[arg(long, value_name = "FILE", default_value = "~/.tx_engine_history")]
+        history: String,
+    },
+    /// Process CSV files dropped into an inbox and move them to an archive.
+    Watch { inbox: String, archive: String },
+    /// Follow a growing CSV file.
+    Tail { input: String },
+    /// Accept CSV streams over TCP.
+    Serve {
+        #[arg(default_value = "127.0.0.1:7878")]
+        address: String,
+        /// Serve Prometheus metrics over HTTP on this address.
+        #[arg(long, value_name = "ADDRESS")]
+        metrics: Option<String>,
+    },
+    /// Serve the HTTP/JSON API.
+    #[cfg(feature = "http")]
+    Http {
+        #[arg(default_value = "127.0.0.1:8080")]
+        address: String,
+        /// Serve Prometheus metrics over HTTP on this address.
+        #[arg(long, value_name = "ADDRESS")]
+        metrics: Option<String>,
+    },
+}
+
 fn main() {
-    env_logger::init();
+    let cli = Cli::try_parse().unwrap_or_else(|e| {
+        // Help and version requests are not errors.
+        let code = if e.use_stderr() { EXIT_ERROR } else { EXIT_OK };
+        let _ = e.print();
+        std::process::exit(code);
+    });
+    init_tracing(&cli.logging);
+
+    let code = match cli.command {
+        Command::Process {
+            input,
+            output,
+            audit,
+            metrics,
+            snapshot,
+            output_format,
+        } => {
+            let mut engine = Engine::new();
+            if let Some(audit) = audit {
+                let audit = normalize(&audit);
+                let sink = JsonLinesAuditSink::open(&audit)
+                    .unwrap_or_else(|e| fail(format_args!("Failed to open {audit}: {e}")));
+                engine.add_observer(sink);
+            }
+            let malformed = process(&mut engine, &input);
+            if let Some(snapshot) = snapshot {
+                save_snapshot(&engine, &normalize(&snapshot));
+            }
+            if let Some(metrics) = metrics {
+                write_metrics(&engine, &normalize(&metrics));
+            }
+
+            info!("Generating report...");
+            let format = report_format(output_format, &output);
+            if engine.accounts.is_empty() {
+                info!("Engine has no accounts to report.");
+            }
+            write_output(&output, |writer| {
+                report::write_report(&engine, format, writer)
+            });
+            exit_code(malformed, &engine)
+        }
+        Command::Validate { input } => validate(&input),
+        Command::Replay {
+            input,
+            output,
+            client,
+            from,
+            to,
+            statement_format,
+        } => {
+            let mut engine = Engine::new().with_history();
+            let malformed = process(&mut engine, &input);
+            let statement =
+                history::client_statement(&engine, client, from..=to.unwrap_or(usize::MAX))
+                    .unwrap_or_else(|e| fail(e));
+            write_output(&output, |writer| statement.write(statement_format, writer));
+            exit_code(malformed, &engine)
+        }
+        Command::Report {
+            input,
+            output,
+            trial_balance,
+            output_format,
+        } => report(&input, &output, trial_balance, output_format),
+        Command::Convert { input, output } => {
+            convert(&normalize(&input), &normalize(&output));
+            EXIT_OK
+        }
+        Command::Generate {
+            output,
+            transactions,
+            clients,
+            mix,
+            fault_rate,
+            seed,
+        } => {
+            generate(
+                &output,
+                WorkloadConfig {
+                    transactions,
+                    clients,
+                    mix: mix.unwrap_or_default(),
+                    fault_rate,
+                    seed,
+                },
+            );
+            EXIT_OK
+        }
+        Command::Reconcile { expected, input } => {
+            let mut engine = Engine::new();
+            let malformed = process(&mut engine, &input);
+            match reconcile(&engine, &normalize(&expected)) {
+                EXIT_OK => exit_code(malformed, &engine),
+                code => code,
+            }
+        }
+        Command::Diff {
+            left,
+            right,
+            dialect,
+            before_dialect,
+            after_dialect,
+        } => {
+            let before_dialect = load_dialect(before_dialect.as_deref().or(dialect.as_deref()));
+            let after_dialect = load_dialect(after_dialect.as_deref().or(dialect.as_deref()));
+            let before = load_snapshot(&normalize(&left), &before_dialect);
+            let after = load_snapshot(&normalize(&right), &after_dialect);
+            diff::diff(&before, &after)
+                .write(&mut std::io::stdout().lock())
+                .unwrap_or_else(|e| fail(format_args!("Failed to write diff: {e}")));
+            EXIT_OK
+        }
+        Command::Repl {
+            input,
+            dialect,
+            script,
+            history,
+        } => {
+            repl(
+                input.as_deref(),
+                dialect.as_deref(),
+                script.as_deref(),
+                &history,
+            );
+            EXIT_OK
+        }
+        Command::Watch { inbox, archive } => {
+            let (inbox, archive) = (normalize(&inbox), normalize(&archive));
+            let mut watcher = InboxWatcher::new(&inbox, &archive)
+                .unwrap_or_else(|e| fail(format_args!("Failed to open archive {archive}: {e}")));
+            let mut engine = watcher.restore_engine().unwrap_or_else(|e| {
+                fail(format_args!(
+                    "Failed to restore the engine from {archive}: {e}"
+                ))
+            });
+            if let Err(e) = watcher.run(&mut engine, Engine::report) {
+                fail(format_args!("Failed to watch {inbox}: {e}"));
+            }
+            EXIT_OK
+        }
+        Command::Tail { input } => {
+            let input = normalize(&input);
+            let result = FileTailer::new(&input).run(&mut Engine::new(), Engine::report);
+            if let Err(e) = result {
+                fail(format_args!("Failed to follow {input}: {e}"));
+            }
+            EXIT_OK
+        }
+        Command::Serve { address, metrics } => {
+            run_until_interrupted(&address, metrics.as_deref(), server::serve);
+            EXIT_OK
+        }
+        #[cfg(feature = "http")]
+        Command::Http { address, metrics } => {
+            run_until_interrupted(
+                &address,
+                metrics.as_deref(),
+                rust_toy_tx_engine::http::serve,
+            );
+            EXIT_OK
+        }
+    };
+    std::process::exit(code);
+}
+
+/// Print `message` and exit with `EXIT_ERROR`.
+fn fail(message: impl Display) -> ! {
+    eprintln!("{message}");
+    std::process::exit(EXIT_ERROR);
+}
+
+/// Install the tracing subscriber, logging to stderr. The level comes from
+/// `--log-level`, then `RUST_LOG`, and defaults to errors only. `--trace-client` and
+/// `--trace-tx` add everything logged while processing matching transactions.
+fn init_tracing(logging: &LoggingArgs) {
+    use tracing_subscriber::EnvFilter;
+
+    let mut filter = match &logging.log_level {
+        Some(level) => EnvFilter::try_new(level)
+            .unwrap_or_else(|e| fail(format_args!("Invalid --log-level '{level}': {e}"))),
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("error")),
+    };
+    let traced = [
+        ("client", logging.trace_client.map(u32::from)),
+        ("tx", logging.trace_tx),
+    ];
+    for (field, id) in traced {
+        if let Some(id) = id {
+            let directive = format!("[transaction{{{field}={id}}}]=trace");
+            filter = filter.add_directive(directive.parse().expect("Valid directive"));
+        }
+    }
 
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() < 2 {
-        eprintln!("Usage: cargo run -- <input.csv>");
-        std::process::exit(1);
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr);
+    match logging.log_format {
+        LogFormat::Text => subscriber.init(),
+        LogFormat::Json => subscriber.json().with_current_span(true).init(),
     }
+}
+
+/// Expand `~` and canonicalize `path`, exiting if that fails.
+fn normalize(path: &str) -> String {
+    input::normalize_path(path).unwrap_or_else(|e| fail(format_args!("{path}: {e}")))
+}
+
+/// The exit code after applying all inputs, see `EXIT_CODES`.
+fn exit_code(malformed: u64, engine: &Engine) -> i32 {
+    let rejected = engine.metrics.rejected_total();
+    if malformed > 0 {
+        eprintln!("{malformed} malformed rows skipped, {rejected} rows rejected");
+        EXIT_MALFORMED
+    } else if rejected > 0 {
+        info!("{rejected} rows rejected");
+        EXIT_REJECTED
+    } else {
+        EXIT_OK
+    }
+}
 
-    // TODO: handle path normalization (expand ~, check canonical form)
-    let input_file = &args[1];
-    info!("Input file: {}", input_file);
+/// Write to `--output`, or standard output if it is not given.
+fn write_output<F>(output: &OutputArgs, write: F)
+where
+    F: FnOnce(&mut dyn Write) -> std::io::Result<()>,
+{
+    let result = match &output.output {
+        Some(path) => {
+            let path = normalize(path);
+            std::fs::File::create(&path).and_then(|file| {
+                let mut writer = std::io::BufWriter::new(file);
+                write(&mut writer)?;
+                writer.flush()
+            })
+        }
+        None => {
+            let mut stdout = std::io::stdout().lock();
+            write(&mut stdout).and_then(|()| stdout.flush())
+        }
+    };
+    if let Err(e) = result {
+        fail(format_args!("Failed to write output: {e}"));
+    }
+}
+
+fn load_dialect(path: Option<&str>) -> CsvDialect {
+    let Some(path) = path else {
+        return CsvDialect::default();
+    };
+    let path = normalize(path);
+    CsvDialect::from_toml_file(&path).unwrap_or_else(|e| fail(format_args!("{path}: {e}")))
+}
+
+/// The normalized input paths, checking that plain files exist.
+fn input_paths(input: &InputArgs) -> Vec<String> {
+    input
+        .inputs
+        .iter()
+        .map(|path| {
+            let normalized = normalize(path);
+            let pattern = normalized.contains(['*', '?', '[']);
+            if !pattern && normalized != input::STDIN_PATH && !Path::new(&normalized).exists() {
+                fail(format_args!("{path}: No such file or directory"));
+            }
+            normalized
+        })
+        .collect()
+}
+
+fn input_format(input: &InputArgs) -> Format {
+    input
+        .format
+        .or_else(|| Format::from_path(&input.inputs[0]))
+        .unwrap_or(Format::Csv)
+}
+
+/// Inputs is what `input_sources` opened: a single file or `-` in any format, or
+/// several CSV files merged into one stream.
+enum Inputs {
+    Single(Box<dyn TransactionSource>),
+    Multiple(Box<MultiFileTransactionSource>),
+}
+
+/// Open the inputs of `input`, exiting if they cannot be read together.
+fn input_sources(input: &InputArgs) -> Inputs {
+    let inputs = input_paths(input);
+    info!("Inputs: {:?}", inputs);
+    let format = input_format(input);
+    let dialect = load_dialect(input.dialect.as_deref());
+    let order = input
+        .order_by
+        .clone()
+        .map_or(FileOrder::Name, FileOrder::Column);
+
+    let single_file = inputs.len() == 1
+        && order == FileOrder::Name
+        && (inputs[0] == input::STDIN_PATH || Path::new(&inputs[0]).is_file());
+    if single_file {
+        let mapping = input.client_mapping.as_deref().map(load_client_mapping);
+        return Inputs::Single(open_source(&inputs[0], format, dialect, mapping));
+    }
+
+    if format != Format::Csv {
+        fail("Multiple inputs are only supported for CSV files.");
+    }
+    let source = MultiFileTransactionSource::from_inputs(&inputs, order)
+        .unwrap_or_else(|e| fail(format_args!("Failed to list input files: {e}")))
+        .with_dialect(dialect);
+    info!("Processing {} files", source.files().len());
+    Inputs::Multiple(Box::new(source))
+}
+
+/// Apply every input to `engine` and return the number of malformed rows.
+fn process(engine: &mut Engine, input: &InputArgs) -> u64 {
+    match input_sources(input) {
+        Inputs::Single(mut source) => {
+            engine.process_transactions(&mut source);
+            source.malformed_rows()
+        }
+        Inputs::Multiple(mut source) => {
+            for rejection in source.process(engine) {
+                eprintln!("{}: {}", rejection.origin, rejection.error);
+            }
+            source.malformed_rows()
+        }
+    }
+}
+
+/// Apply the inputs to a scratch engine and print what they would do.
+fn validate(input: &InputArgs) -> i32 {
+    let mut source = match input_sources(input) {
+        Inputs::Single(source) => source,
+        Inputs::Multiple(source) => source,
+    };
+    let report = validate::validate(&mut source);
+    report
+        .write(&mut std::io::stdout().lock())
+        .unwrap_or_else(|e| fail(format_args!("Failed to write output: {e}")));
+    if report.malformed > 0 {
+        EXIT_MALFORMED
+    } else if report.rejected_total() > 0 {
+        EXIT_REJECTED
+    } else {
+        EXIT_OK
+    }
+}
+
+/// The account report format: `--output-format`, else the extension of `--output`,
+/// else CSV.
+fn report_format(output_format: Option<Format>, output: &OutputArgs) -> Format {
+    let format = output_format
+        .or_else(|| output.output.as_deref().and_then(Format::from_path))
+        .unwrap_or(Format::Csv);
+    match format {
+        Format::Binary => fail("Reports cannot be written in the binary format."),
+        Format::Ofx | Format::Camt053 => fail("Reports cannot be written as bank statements."),
+        Format::Csv | Format::JsonLines => format,
+    }
+}
+
+/// Write the account report of the inputs or of a single `.json` snapshot.
+fn report(
+    input: &InputArgs,
+    output: &OutputArgs,
+    trial_balance: bool,
+    output_format: Option<Format>,
+) -> i32 {
+    let inputs = input_paths(input);
+    let format = report_format(output_format, output);
+    if inputs.len() == 1 && inputs[0].ends_with(".json") {
+        if trial_balance {
+            fail("Snapshots have no ledger, the trial balance needs the inputs.");
+        }
+        let snapshot = load_snapshot(&inputs[0], &CsvDialect::default());
+        write_output(output, |writer| {
+            report::write_account_summaries(&snapshot.accounts, format, writer)
+        });
+        return EXIT_OK;
+    }
 
     let mut engine = Engine::new();
-    let mut source = CsvTransactionSource::new(input_file);
-    run_engine_with_source(&mut engine, &mut source);
+    let malformed = process(&mut engine, input);
+    write_output(output, |writer| {
+        if trial_balance {
+            engine.ledger.trial_balance().write(writer)
+        } else {
+            report::write_report(&engine, format, writer)
+        }
+    });
+    exit_code(malformed, &engine)
+}
+
+/// Open a transaction source for a single file or `-` in the given format.
+/// Open a single input. Bank statements need the client `mapping`.
+fn open_source(
+    path: &str,
+    format: Format,
+    dialect: CsvDialect,
+    mapping: Option<ClientMapping>,
+) -> Box<dyn TransactionSource> {
+    let statement = |format| {
+        let mapping = mapping.unwrap_or_else(|| {
+            fail(format_args!(
+                "{path}: Bank statements need a --client-mapping file."
+            ))
+        });
+        Box::new(StatementTransactionSource::new(path, format, mapping))
+    };
+    match format {
+        Format::Csv => Box::new(CsvTransactionSource::new(path).with_dialect(dialect)),
+        Format::JsonLines => Box::new(JsonLinesTransactionSource::new(path)),
+        Format::Binary => Box::new(BinaryTransactionSource::new(path)),
+        Format::Ofx => statement(StatementFormat::Ofx),
+        Format::Camt053 => statement(StatementFormat::Camt053),
+    }
+}
+
+fn load_client_mapping(path: &str) -> ClientMapping {
+    let path = normalize(path);
+    ClientMapping::from_toml_file(&path).unwrap_or_else(|e| fail(format_args!("{path}: {e}")))
+}
+
+/// Compare the engine's accounts with the expected balances in `expected_file` and
+/// print any mismatches. Returns `EXIT_MISMATCH` if there are any.
+fn reconcile(engine: &Engine, expected_file: &str) -> i32 {
+    let expected = input::open(expected_file)
+        .map_err(|e| e.to_string())
+        .and_then(|reader| reconcile::read_expected(reader).map_err(|e| e.to_string()))
+        .unwrap_or_else(|e| fail(format_args!("Failed to read {expected_file}: {e}")));
+    let mismatches = reconcile::reconcile(engine, &expected)
+        .unwrap_or_else(|e| fail(format_args!("{expected_file}: {e}")));
+    if mismatches.is_empty() {
+        info!("All {} clients match {expected_file}", expected.len());
+        return EXIT_OK;
+    }
+    reconcile::write_mismatches(&mismatches, &mut std::io::stdout().lock())
+        .unwrap_or_else(|e| fail(format_args!("Failed to write mismatches: {e}")));
+    EXIT_MISMATCH
+}
+
+/// Start a shell on `input`, reading commands from `script` or the terminal.
+fn repl(input: Option<&str>, dialect: Option<&str>, script: Option<&str>, history: &str) {
+    let mut repl = match input.map(normalize) {
+        Some(path) if path.ends_with(".json") => {
+            Repl::from_snapshot(load_snapshot(&path, &CsvDialect::default()))
+        }
+        Some(path) => {
+            let format = Format::from_path(&path).unwrap_or(Format::Csv);
+            let mut repl = Repl::new();
+            repl.load(&mut open_source(&path, format, load_dialect(dialect), None));
+            repl
+        }
+        None => Repl::new(),
+    };
+
+    let result = match script.map(normalize) {
+        Some(path) => std::fs::File::open(&path)
+            .and_then(|file| {
+                repl.run_script(std::io::BufReader::new(file), &mut std::io::stdout().lock())
+            })
+            .map_err(|e| format!("Failed to run script {path}: {e}")),
+        None => repl
+            .run_interactive(Some(Path::new(&normalize(history))))
+            .map_err(|e| format!("Shell failed: {e}")),
+    };
+    if let Err(message) = result {
+        fail(message);
+    }
+}
+
+/// Load a snapshot from a `.json` file, or build one by processing any other input.
+fn load_snapshot(path: &str, dialect: &CsvDialect) -> Snapshot {
+    if Path::new(path).extension().is_some_and(|ext| ext == "json") {
+        let file = std::fs::File::open(path)
+            .unwrap_or_else(|e| fail(format_args!("Failed to open {path}: {e}")));
+        return Snapshot::read_json(std::io::BufReader::new(file))
+            .unwrap_or_else(|e| fail(format_args!("Invalid snapshot {path}: {e}")));
+    }
+
+    let format = Format::from_path(path).unwrap_or(Format::Csv);
+    let mut engine = Engine::new();
+    engine.process_transactions(&mut open_source(path, format, dialect.clone(), None));
+    Snapshot::from_engine(&engine)
+}
+
+/// Save the accounts and recorded transactions of `engine` to `path` as JSON.
+fn save_snapshot(engine: &Engine, path: &str) {
+    let file = std::fs::File::create(path)
+        .unwrap_or_else(|e| fail(format_args!("Failed to create {path}: {e}")));
+    Snapshot::from_engine(engine)
+        .write_json(std::io::BufWriter::new(file))
+        .unwrap_or_else(|e| fail(format_args!("Failed to write {path}: {e}")));
+    info!("Saved snapshot to {path}");
+}
+
+/// Dump the metrics of `engine` to `path` in the Prometheus text format.
+fn write_metrics(engine: &Engine, path: &str) {
+    let result = std::fs::File::create(path).and_then(|file| {
+        let mut writer = std::io::BufWriter::new(file);
+        engine.metrics.write_prometheus(&mut writer)?;
+        writer.flush()
+    });
+    if let Err(e) = result {
+        fail(format_args!("Failed to write {path}: {e}"));
+    }
+}
+
+/// Translate transactions between formats, detected from the file extensions.
+fn convert(input_file: &str, output_file: &str) {
+    let format_of = |path: &str| {
+        Format::from_path(path).unwrap_or_else(|| {
+            fail(format_args!(
+                "Cannot tell the format of {path}, expected .csv, .jsonl or .txb"
+            ))
+        })
+    };
+    let mut source = open_source(
+        input_file,
+        format_of(input_file),
+        CsvDialect::default(),
+        None,
+    );
+    let output_format = format_of(output_file);
+
+    let output = std::fs::File::create(output_file)
+        .unwrap_or_else(|e| fail(format_args!("Failed to create {output_file}: {e}")));
+    let written = convert::write_transactions(
+        source.transactions(),
+        output_format,
+        std::io::BufWriter::new(output),
+    )
+    .unwrap_or_else(|e| fail(format_args!("Failed to write {output_file}: {e}")));
+    info!("Wrote {written} transactions to {output_file}");
+}
+
+/// Write the rows of a synthetic workload to `--output`.
+fn generate(output: &OutputArgs, config: WorkloadConfig) {
+    if !(0.0..=1.0).contains(&config.fault_rate) {
+        fail(format_args!(
+            "--fault-rate must be between 0 and 1, got {}",
+            config.fault_rate
+        ));
+    }
+    if config.clients == 0 {
+        fail("--clients must be at least 1");
+    }
+    let format = output
+        .output
+        .as_deref()
+        .and_then(Format::from_path)
+        .unwrap_or(Format::Csv);
+
+    let mut workload = Workload::new(config);
+    write_output(output, |writer| {
+        let written = convert::write_transactions(&mut workload, format, writer)
+            .map_err(std::io::Error::other)?;
+        info!("Generated {written} transactions");
+        Ok(())
+    });
+    for fault in Fault::ALL {
+        info!("{} faults: {}", fault.code(), workload.faults(fault));
+    }
+}
+
+/// Serve on `address` until interrupted, then print the final report. With
+/// `metrics_address`, Prometheus metrics are served there as well.
+fn run_until_interrupted<F, Fut>(address: &str, metrics_address: Option<&str>, serve: F)
+where
+    F: FnOnce(tokio::net::TcpListener, SharedEngine) -> Fut,
+    Fut: Future<Output = std::io::Result<()>>,
+{
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to start tokio runtime");
+    let engine = SharedEngine::default();
+
+    runtime.block_on(async {
+        let bind = |address: &str| {
+            let address = address.to_string();
+            async move {
+                tokio::net::TcpListener::bind(&address)
+                    .await
+                    .unwrap_or_else(|e| fail(format_args!("Failed to listen on {address}: {e}")))
+            }
+        };
+        let listener = bind(address).await;
+        if let Some(metrics_address) = metrics_address {
+            #[cfg(feature = "http")]
+            tokio::spawn(rust_toy_tx_engine::metrics::serve_metrics(
+                bind(metrics_address).await,
+                Arc::clone(&engine),
+            ));
+            #[cfg(not(feature = "http"))]
+            fail(format_args!(
+                "Serving metrics on {metrics_address} needs the `http` feature."
+            ));
+        }
+
+        tokio::select! {
+            result = serve(listener, Arc::clone(&engine)) => {
+                if let Err(e) = result {
+                    eprintln!("Server error: {e}");
+                }
+            }
+            _ = tokio::signal::ctrl_c() => info!("Shutting down..."),
+        }
+    });
 
     info!("Generating report...");
-    engine.report();
+    engine.lock().expect("Engine lock poisoned").report();
 }
@@ -0,0 +1,354 @@
+//! Metrics of processed and rejected transactions in the Prometheus text format.
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::time::Duration;
+
+#[cfg(feature = "http")]
+use log::info;
+use rust_decimal::Decimal;
+#[cfg(feature = "http")]
+use tokio::net::TcpListener;
+
+use crate::engine::{Engine, EngineError};
+use crate::history::Balances;
+#[cfg(feature = "http")]
+use crate::server::SharedEngine;
+use crate::transaction::{DisputeState, TransactionType};
+
+/// Upper bounds in seconds of the processing latency histogram buckets.
+pub const LATENCY_BUCKETS: [f64; 6] = [1e-6, 5e-6, 1e-5, 5e-5, 1e-4, 1e-3];
+
+/// Metrics counts the transactions an engine processed. Counters only ever grow, the
+/// gauges follow the state of the engine.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metrics {
+    /// Rows processed by type, whether applied or rejected.
+    processed: [u64; TransactionType::ALL.len()],
+    /// Rejections by `EngineError::code`.
+    rejected: BTreeMap<&'static str, u64>,
+    open_disputes: u64,
+    locked_accounts: u64,
+    held_amount: Decimal,
+    // Non-cumulative bucket counts, the last one is for latencies above every bound.
+    latency_buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    latency_sum: Duration,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record one transaction. `before` and `after` are the balances of the client's
+    /// account around the transaction and `elapsed` is the time it took to apply. The
+    /// balances may be `None` for deposits and withdrawals, which only change available
+    /// funds.
+    pub fn record(
+        &mut self,
+        r#type: TransactionType,
+        result: Result<(), &EngineError>,
+        before: Option<&Balances>,
+        after: Option<&Balances>,
+        elapsed: Duration,
+    ) {
+        self.processed[type_index(r#type)] += 1;
+        let seconds = elapsed.as_secs_f64();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.latency_buckets[bucket] += 1;
+        self.latency_sum += elapsed;
+
+        if let Err(e) = result {
+            *self.rejected.entry(e.code()).or_default() += 1;
+            return;
+        }
+        match r#type {
+            TransactionType::Dispute => self.open_disputes += 1,
+            TransactionType::Resolve | TransactionType::Chargeback => self.open_disputes -= 1,
+            TransactionType::Deposit | TransactionType::Withdrawal => {}
+        }
+        let held = |balances: Option<&Balances>| balances.map_or(Decimal::ZERO, |b| b.held);
+        self.held_amount += held(after) - held(before);
+        let locked = |balances: Option<&Balances>| balances.is_some_and(|b| b.locked);
+        if locked(after) && !locked(before) {
+            self.locked_accounts += 1;
+        }
+    }
+
+    /// Set the gauges from the state of `engine`, e.g. one restored from a snapshot,
+    /// so that later transactions update them from the right starting point.
+    pub fn sync_gauges(&mut self, engine: &Engine) {
+        self.open_disputes = engine
+            .transactions
+            .values()
+            .filter(|record| record.dispute_state == DisputeState::Disputed)
+            .count() as u64;
+        self.locked_accounts = engine.accounts.values().filter(|a| a.is_locked).count() as u64;
+        self.held_amount = engine.accounts.values().map(|a| a.held()).sum();
+    }
+
+    /// Rows of type `r#type` processed so far.
+    pub fn processed(&self, r#type: TransactionType) -> u64 {
+        self.processed[type_index(r#type)]
+    }
+
+    /// Rejections with the given `EngineError::code` so far.
+    pub fn rejected(&self, code: &str) -> u64 {
+        self.rejected.get(code).copied().unwrap_or_default()
+    }
+
+    /// Rejections for any reason so far.
+    pub fn rejected_total(&self) -> u64 {
+        self.rejected.values().sum()
+    }
+
+    pub fn open_disputes(&self) -> u64 {
+        self.open_disputes
+    }
+
+    pub fn locked_accounts(&self) -> u64 {
+        self.locked_accounts
+    }
+
+    pub fn held_amount(&self) -> Decimal {
+        self.held_amount
+    }
+
+    /// Add the counts of `other`, e.g. metrics kept by another shard.
+    pub fn merge(&mut self, other: &Metrics) {
+        for (count, other) in self.processed.iter_mut().zip(other.processed) {
+            *count += other;
+        }
+        for (code, count) in &other.rejected {
+            *self.rejected.entry(code).or_default() += count;
+        }
+        self.open_disputes += other.open_disputes;
+        self.locked_accounts += other.locked_accounts;
+        self.held_amount += other.held_amount;
+        for (count, other) in self.latency_buckets.iter_mut().zip(other.latency_buckets) {
+            *count += other;
+        }
+        self.latency_sum += other.latency_sum;
+    }
+
+    /// Write all metrics in the Prometheus text exposition format.
+    pub fn write_prometheus<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(
+            writer,
+            "# HELP tx_engine_transactions_processed_total Transactions processed, by type."
+        )?;
+        writeln!(
+            writer,
+            "# TYPE tx_engine_transactions_processed_total counter"
+        )?;
+        for r#type in TransactionType::ALL {
+            writeln!(
+                writer,
+                "tx_engine_transactions_processed_total{{type=\"{}\"}} {}",
+                r#type.name(),
+                self.processed(r#type)
+            )?;
+        }
+
+        writeln!(
+            writer,
+            "# HELP tx_engine_transactions_rejected_total Transactions rejected, by reason."
+        )?;
+        writeln!(
+            writer,
+            "# TYPE tx_engine_transactions_rejected_total counter"
+        )?;
+        for (code, count) in &self.rejected {
+            writeln!(
+                writer,
+                "tx_engine_transactions_rejected_total{{reason=\"{code}\"}} {count}"
+            )?;
+        }
+
+        let gauges = [
+            (
+                "open_disputes",
+                "Transactions currently in dispute.",
+                self.open_disputes.to_string(),
+            ),
+            (
+                "locked_accounts",
+                "Accounts locked by a chargeback.",
+                self.locked_accounts.to_string(),
+            ),
+            (
+                "held_amount",
+                "Funds held by open disputes over all accounts.",
+                self.held_amount.to_string(),
+            ),
+        ];
+        for (name, help, value) in gauges {
+            writeln!(writer, "# HELP tx_engine_{name} {help}")?;
+            writeln!(writer, "# TYPE tx_engine_{name} gauge")?;
+            writeln!(writer, "tx_engine_{name} {value}")?;
+        }
+
+        writeln!(
+            writer,
+            "# HELP tx_engine_apply_duration_seconds Time taken to apply a transaction."
+        )?;
+        writeln!(writer, "# TYPE tx_engine_apply_duration_seconds histogram")?;
+        let mut cumulative = 0;
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(self.latency_buckets) {
+            cumulative += count;
+            writeln!(
+                writer,
+                "tx_engine_apply_duration_seconds_bucket{{le=\"{bound}\"}} {cumulative}"
+            )?;
+        }
+        let count: u64 = self.latency_buckets.iter().sum();
+        writeln!(
+            writer,
+            "tx_engine_apply_duration_seconds_bucket{{le=\"+Inf\"}} {count}"
+        )?;
+        writeln!(
+            writer,
+            "tx_engine_apply_duration_seconds_sum {}",
+            self.latency_sum.as_secs_f64()
+        )?;
+        writeln!(writer, "tx_engine_apply_duration_seconds_count {count}")
+    }
+}
+
+fn type_index(r#type: TransactionType) -> usize {
+    TransactionType::ALL
+        .iter()
+        .position(|t| *t == r#type)
+        .expect("Every transaction type is listed")
+}
+
+// Metrics are served with the HTTP API, which needs the `http` feature.
+#[cfg(feature = "http")]
+pub(crate) async fn get_metrics(
+    axum::extract::State(engine): axum::extract::State<SharedEngine>,
+) -> impl axum::response::IntoResponse {
+    let mut body = Vec::new();
+    engine
+        .lock()
+        .expect("Engine lock poisoned")
+        .metrics
+        .write_prometheus(&mut body)
+        .expect("Writing to a Vec cannot fail");
+    (
+        [(
+            axum::http::header::CONTENT_TYPE,
+            "text/plain; version=0.0.4",
+        )],
+        body,
+    )
+}
+
+/// Serve the current metrics of `engine` at `GET /metrics` on `listener` until the
+/// task is cancelled.
+#[cfg(feature = "http")]
+pub async fn serve_metrics(listener: TcpListener, engine: SharedEngine) -> io::Result<()> {
+    info!("Serving metrics on {}", listener.local_addr()?);
+    let router = axum::Router::new()
+        .route("/metrics", axum::routing::get(get_metrics))
+        .with_state(engine);
+    axum::serve(listener, router).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::Snapshot;
+    use crate::test_support::{apply_all, tx};
+
+    fn engine() -> Engine {
+        use TransactionType::*;
+        apply_all(
+            Engine::new(),
+            [
+                tx(Deposit, 1, 1, Some(500)),
+                tx(Deposit, 2, 2, Some(300)),
+                tx(Withdrawal, 1, 3, Some(900)),
+                tx(Deposit, 1, 1, Some(100)),
+                tx(Dispute, 1, 1, None),
+                tx(Dispute, 2, 2, None),
+                tx(Chargeback, 2, 2, None),
+                tx(Deposit, 2, 4, Some(100)),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_counts_and_gauges() {
+        let metrics = &engine().metrics;
+        assert_eq!(metrics.processed(TransactionType::Deposit), 4);
+        assert_eq!(metrics.processed(TransactionType::Dispute), 2);
+        assert_eq!(metrics.rejected("insufficient_funds"), 1);
+        assert_eq!(metrics.rejected("duplicate_transaction"), 1);
+        assert_eq!(metrics.rejected("account_locked"), 1);
+        assert_eq!(metrics.open_disputes(), 1);
+        assert_eq!(metrics.locked_accounts(), 1);
+        assert_eq!(metrics.held_amount(), Decimal::new(500, 2));
+
+        let mut output = Vec::new();
+        metrics.write_prometheus(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        for line in [
+            "tx_engine_transactions_processed_total{type=\"deposit\"} 4",
+            "tx_engine_transactions_processed_total{type=\"resolve\"} 0",
+            "tx_engine_transactions_rejected_total{reason=\"account_locked\"} 1",
+            "tx_engine_open_disputes 1",
+            "tx_engine_held_amount 5.00",
+            "tx_engine_apply_duration_seconds_bucket{le=\"+Inf\"} 8",
+            "tx_engine_apply_duration_seconds_count 8",
+        ] {
+            assert!(output.lines().any(|l| l == line), "missing {line}");
+        }
+    }
+
+    #[test]
+    fn test_gauges_after_restore() {
+        let engine = engine();
+        let mut restored = Snapshot::from_engine(&engine).restore();
+        assert_eq!(restored.metrics.open_disputes(), 1);
+        assert_eq!(restored.metrics.locked_accounts(), 1);
+        assert_eq!(restored.metrics.held_amount(), Decimal::new(500, 2));
+
+        restored
+            .apply_transaction(tx(TransactionType::Resolve, 1, 1, None))
+            .unwrap();
+        assert_eq!(restored.metrics.open_disputes(), 0);
+        assert_eq!(restored.metrics.held_amount(), Decimal::ZERO);
+    }
+
+    #[cfg(feature = "http")]
+    #[tokio::test]
+    async fn test_serve_metrics() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::TcpStream;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let engine = SharedEngine::new(std::sync::Mutex::new(engine()));
+        tokio::spawn(serve_metrics(listener, engine));
+
+        let get = |path: &'static str| async move {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            let request =
+                format!("GET {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        };
+
+        let response = get("/metrics").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("content-type: text/plain; version=0.0.4\r\n"));
+        assert!(response.contains("\r\n\r\n# HELP tx_engine_transactions_processed_total"));
+        assert!(response.contains("\ntx_engine_locked_accounts 1\n"));
+        assert!(get("/").await.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+}
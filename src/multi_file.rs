@@ -0,0 +1,485 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::fmt;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+
+use csv::StringRecordsIntoIter;
+use log::warn;
+use tracing::info_span;
+
+use crate::dialect::{CsvDialect, ParseError, RowParser};
+use crate::engine::{Engine, EngineError};
+use crate::input;
+use crate::report::Format;
+use crate::transaction::{MalformedRows, Transaction, TransactionSource};
+
+/// FileOrder defines the order in which rows from several files are applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileOrder {
+    /// Apply files one after another, sorted by file name.
+    Name,
+    /// Merge rows from all files by the value of this column, e.g. a sequence number
+    /// or an ISO 8601 timestamp. Each file must already be sorted by the column.
+    Column(String),
+}
+
+/// Origin identifies the file and line a transaction was read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Origin {
+    pub file: PathBuf,
+    pub line: u64,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file.display(), self.line)
+    }
+}
+
+/// Rejection is a transaction the engine rejected, with where it came from.
+#[derive(Debug)]
+pub struct Rejection {
+    pub origin: Origin,
+    pub transaction: Transaction,
+    pub error: EngineError,
+}
+
+/// MultiFileTransactionSource reads CSV transactions from many files in a defined order.
+/// Like the other sources, the files are read once.
+pub struct MultiFileTransactionSource {
+    files: Vec<PathBuf>,
+    // The files, until the first call to `transactions` or `process`.
+    unread: Option<Vec<PathBuf>>,
+    order: FileOrder,
+    dialect: CsvDialect,
+    malformed: MalformedRows,
+}
+
+impl MultiFileTransactionSource {
+    /// Create a source over `files`, which are sorted by file name.
+    pub fn new(mut files: Vec<PathBuf>, order: FileOrder) -> Self {
+        files.sort_by(|a, b| a.file_name().cmp(&b.file_name()).then_with(|| a.cmp(b)));
+        Self {
+            unread: Some(files.clone()),
+            files,
+            order,
+            dialect: CsvDialect::default(),
+            malformed: MalformedRows::default(),
+        }
+    }
+
+    /// Read every file using `dialect` instead of the default layout.
+    pub fn with_dialect(mut self, dialect: CsvDialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    /// Create a source from paths, directories and glob patterns, see `expand_inputs`.
+    pub fn from_inputs(inputs: &[String], order: FileOrder) -> std::io::Result<Self> {
+        Ok(Self::new(expand_inputs(inputs)?, order))
+    }
+
+    /// The files this source reads, in name order.
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Transactions with the file and line each one was read from. Empty after the
+    /// first call.
+    pub fn transactions_with_origin(&mut self) -> Box<dyn Iterator<Item = (Transaction, Origin)>> {
+        let Some(files) = self.unread.take() else {
+            return Box::new(std::iter::empty());
+        };
+        let dialect = self.dialect.clone();
+        let malformed = self.malformed.clone();
+        match &self.order {
+            FileOrder::Name => Box::new(
+                files
+                    .into_iter()
+                    .filter_map(move |file| FileRows::open(file, &dialect, None, malformed.clone()))
+                    .flatten()
+                    .map(|row| (row.transaction, row.origin)),
+            ),
+            FileOrder::Column(column) => {
+                let readers = files
+                    .into_iter()
+                    .filter_map(|file| {
+                        FileRows::open(file, &dialect, Some(column), malformed.clone())
+                    })
+                    .collect();
+                Box::new(MergedRows::new(readers).map(|row| (row.transaction, row.origin)))
+            }
+        }
+    }
+
+    /// Apply every transaction to `engine` and return the rejected ones. Each rejection
+    /// is also logged with the file and line it came from.
+    pub fn process(&mut self, engine: &mut Engine) -> Vec<Rejection> {
+        let _span = info_span!("source", files = self.files.len()).entered();
+        let mut rejections = Vec::new();
+        for (transaction, origin) in self.transactions_with_origin() {
+            if let Err(error) = engine.apply_transaction(transaction.clone()) {
+                warn!("Rejected row at {}: {}", origin, error);
+                rejections.push(Rejection {
+                    origin,
+                    transaction,
+                    error,
+                });
+            }
+        }
+        rejections
+    }
+}
+
+impl TransactionSource for MultiFileTransactionSource {
+    fn transactions(&mut self) -> Box<dyn Iterator<Item = Transaction>> {
+        Box::new(self.transactions_with_origin().map(|(tx, _)| tx))
+    }
+
+    fn malformed(&self) -> MalformedRows {
+        self.malformed.clone()
+    }
+}
+
+/// Whether `path` names a CSV file, possibly compressed, e.g. `batch.csv.gz`.
+pub(crate) fn is_csv_path(path: &Path) -> bool {
+    Format::from_path(&path.to_string_lossy()) == Some(Format::Csv)
+}
+
+/// Expand paths, directories and glob patterns into a list of files. Directories
+/// contribute the CSV files directly inside them, including compressed ones. A
+/// directory or pattern without any matching file is an error.
+pub fn expand_inputs(inputs: &[String]) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for pattern in inputs {
+        let path = Path::new(pattern);
+        let found = files.len();
+        if path.is_dir() {
+            for entry in std::fs::read_dir(path)? {
+                let entry = entry?;
+                if is_csv_path(&entry.path()) && entry.file_type()?.is_file() {
+                    files.push(entry.path());
+                }
+            }
+        } else if pattern.contains(['*', '?', '[']) {
+            let paths = glob::glob(pattern)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+            for path in paths {
+                let path = path.map_err(std::io::Error::from)?;
+                if path.is_file() {
+                    files.push(path);
+                }
+            }
+        } else {
+            files.push(path.to_path_buf());
+            continue;
+        }
+        if files.len() == found {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("No CSV files found for '{pattern}'"),
+            ));
+        }
+    }
+    Ok(files)
+}
+
+/// OrderKey is a value of the ordering column. Integers compare numerically, anything
+/// else (such as ISO 8601 timestamps) compares as text.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum OrderKey {
+    Number(i64),
+    Text(String),
+}
+
+impl OrderKey {
+    fn parse(value: &str) -> Self {
+        value
+            .parse()
+            .map_or_else(|_| OrderKey::Text(value.to_string()), OrderKey::Number)
+    }
+}
+
+struct Row {
+    transaction: Transaction,
+    origin: Origin,
+    key: Option<OrderKey>,
+}
+
+/// FileRows streams rows from one CSV file, optionally reading an ordering column.
+struct FileRows {
+    file: PathBuf,
+    parser: RowParser,
+    key_index: Option<usize>,
+    records: StringRecordsIntoIter<Box<dyn BufRead>>,
+    malformed: MalformedRows,
+}
+
+impl FileRows {
+    /// Open `file` and read its header. A file that cannot be opened or whose header
+    /// cannot be parsed is skipped and counted in `malformed`.
+    fn open(
+        file: PathBuf,
+        dialect: &CsvDialect,
+        key_column: Option<&String>,
+        malformed: MalformedRows,
+    ) -> Option<Self> {
+        let display = file.display().to_string();
+        let reader = match input::open(&display) {
+            Ok(reader) => reader,
+            Err(e) => {
+                malformed.skip(&display, e);
+                return None;
+            }
+        };
+
+        let mut rdr = dialect.reader_builder().from_reader(reader);
+        let header = || format!("{display}:header");
+        let headers = match rdr.headers() {
+            Ok(headers) => headers.clone(),
+            Err(e) => {
+                malformed.skip(header(), e);
+                return None;
+            }
+        };
+        let parser = match dialect.row_parser(&headers) {
+            Ok(parser) => parser,
+            Err(e) => {
+                malformed.skip(header(), e);
+                return None;
+            }
+        };
+
+        let key_index = match key_column {
+            None => None,
+            Some(column) => match headers
+                .iter()
+                .position(|header| header.trim().eq_ignore_ascii_case(column))
+            {
+                Some(index) => Some(index),
+                None => {
+                    malformed.skip(header(), ParseError::MissingColumn(column.clone()));
+                    return None;
+                }
+            },
+        };
+
+        Some(Self {
+            file,
+            parser,
+            key_index,
+            records: rdr.into_records(),
+            malformed,
+        })
+    }
+}
+
+impl Iterator for FileRows {
+    type Item = Row;
+
+    // Malformed rows are skipped. An I/O error ends the file, since the reader cannot
+    // recover from it.
+    fn next(&mut self) -> Option<Row> {
+        loop {
+            let record = match self.records.next()? {
+                Ok(record) => record,
+                Err(e) => {
+                    let line = e.position().map_or(0, csv::Position::line);
+                    let io_error = matches!(e.kind(), csv::ErrorKind::Io(_));
+                    self.malformed
+                        .skip(format_args!("{}:{}", self.file.display(), line), e);
+                    if io_error {
+                        return None;
+                    }
+                    continue;
+                }
+            };
+            let origin = Origin {
+                file: self.file.clone(),
+                line: record.position().map_or(0, |p| p.line()),
+            };
+            let transaction = match self.parser.parse(&record) {
+                Ok(transaction) => transaction,
+                Err(e) => {
+                    self.malformed.skip(&origin, e);
+                    continue;
+                }
+            };
+            let key = self
+                .key_index
+                .map(|index| OrderKey::parse(record.get(index).unwrap_or_default()));
+
+            return Some(Row {
+                transaction,
+                origin,
+                key,
+            });
+        }
+    }
+}
+
+// Heap entry for the k-way merge. Ordered by key, then by file so that rows with
+// equal keys are applied in file name order.
+struct MergeEntry {
+    row: Row,
+    file_index: usize,
+}
+
+impl MergeEntry {
+    fn sort_key(&self) -> (&Option<OrderKey>, usize) {
+        (&self.row.key, self.file_index)
+    }
+}
+
+impl PartialEq for MergeEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.sort_key() == other.sort_key()
+    }
+}
+
+impl Eq for MergeEntry {}
+
+impl PartialOrd for MergeEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for MergeEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // BinaryHeap is a max-heap, reverse to pop the smallest key first.
+        other.sort_key().cmp(&self.sort_key())
+    }
+}
+
+/// MergedRows performs a k-way merge of several files sorted by their order key.
+struct MergedRows {
+    readers: Vec<FileRows>,
+    heap: BinaryHeap<MergeEntry>,
+}
+
+impl MergedRows {
+    fn new(mut readers: Vec<FileRows>) -> Self {
+        let heap = readers
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(file_index, reader)| {
+                reader.next().map(|row| MergeEntry { row, file_index })
+            })
+            .collect();
+        Self { readers, heap }
+    }
+}
+
+impl Iterator for MergedRows {
+    type Item = Row;
+
+    fn next(&mut self) -> Option<Row> {
+        let MergeEntry { row, file_index } = self.heap.pop()?;
+        if let Some(next) = self.readers[file_index].next() {
+            self.heap.push(MergeEntry {
+                row: next,
+                file_index,
+            });
+        }
+        Some(row)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+    use rust_decimal::Decimal;
+    use std::fs;
+
+    #[test]
+    fn test_files_applied_in_name_order() {
+        let dir = temp_dir("multi-name");
+        // The withdrawal in 02 only succeeds if 01 is applied first.
+        fs::write(
+            dir.join("02.csv"),
+            "type,client,tx,amount\nwithdrawal,1,2,4.0\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("01.csv"),
+            "type,client,tx,amount\ndeposit,1,1,5.0\n",
+        )
+        .unwrap();
+        fs::write(dir.join("README"), "not an input").unwrap();
+
+        let mut source =
+            MultiFileTransactionSource::from_inputs(&[dir.display().to_string()], FileOrder::Name)
+                .unwrap();
+        let mut engine = Engine::new();
+        let rejections = source.process(&mut engine);
+
+        assert!(rejections.is_empty());
+        assert_eq!(source.malformed_rows(), 0);
+        assert_eq!(engine.accounts[&1].total(), Decimal::new(10, 1));
+
+        // The files are read once.
+        assert_eq!(source.transactions().count(), 0);
+        assert_eq!(source.files().len(), 2);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_merge_by_column_and_rejection_origin() {
+        let dir = temp_dir("multi-merge");
+        fs::write(
+            dir.join("a.csv"),
+            "seq,type,client,tx,amount\n1,deposit,1,1,5.0\n4,withdrawal,1,4,9.0\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("b.csv"),
+            " Seq ,type,client,tx,amount\n2,withdrawal,1,2,3.0\n3,deposit,1,3,1.0\n",
+        )
+        .unwrap();
+
+        let pattern = dir.join("*.csv").display().to_string();
+        let source = || {
+            MultiFileTransactionSource::from_inputs(
+                std::slice::from_ref(&pattern),
+                FileOrder::Column("seq".to_string()),
+            )
+            .unwrap()
+        };
+
+        let order: Vec<u32> = source().transactions().map(|tx| tx.tx).collect();
+        assert_eq!(order, vec![1, 2, 3, 4]);
+
+        let mut source = source();
+        let mut engine = Engine::new();
+        let rejections = source.process(&mut engine);
+        assert_eq!(rejections.len(), 1);
+        assert_eq!(rejections[0].transaction.tx, 4);
+        assert_eq!(
+            rejections[0].origin,
+            Origin {
+                file: dir.join("a.csv"),
+                line: 3
+            }
+        );
+        assert_eq!(engine.accounts[&1].total(), Decimal::new(30, 1));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_inputs_without_files_are_errors() {
+        let dir = temp_dir("multi-empty");
+        fs::write(dir.join("notes.txt"), "not an input").unwrap();
+
+        for input in [
+            dir.display().to_string(),
+            dir.join("*.csv").display().to_string(),
+        ] {
+            let error = expand_inputs(&[input]).unwrap_err();
+            assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
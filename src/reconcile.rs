@@ -0,0 +1,218 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use thiserror::Error;
+
+use crate::account::AccountSummary;
+use crate::engine::Engine;
+
+/// Mismatch is one difference between the expected balances and the engine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mismatch {
+    /// A field of a client present on both sides differs.
+    Field {
+        client: u16,
+        field: &'static str,
+        expected: String,
+        actual: String,
+    },
+    /// The client is expected but the engine has no account for it.
+    MissingClient(u16),
+    /// The engine has an account for a client that is not expected.
+    ExtraClient(u16),
+}
+
+impl Mismatch {
+    pub fn client(&self) -> u16 {
+        match self {
+            Mismatch::Field { client, .. }
+            | Mismatch::MissingClient(client)
+            | Mismatch::ExtraClient(client) => *client,
+        }
+    }
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::Field {
+                client,
+                field,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "client {client}: {field} expected {expected}, got {actual}"
+            ),
+            Mismatch::MissingClient(client) => write!(f, "client {client}: missing"),
+            Mismatch::ExtraClient(client) => write!(f, "client {client}: not expected"),
+        }
+    }
+}
+
+/// ReconcileError represents an expected-balances file that cannot be read.
+#[derive(Debug, Error)]
+pub enum ReconcileError {
+    #[error("Invalid expected balances: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("Client {0} is listed more than once.")]
+    DuplicateClient(u16),
+}
+
+/// Read expected balances in the account report format
+/// (`client, available, held, total, locked`).
+pub fn read_expected<R: Read>(reader: R) -> Result<Vec<AccountSummary>, ReconcileError> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+    rdr.deserialize()
+        .map(|row| row.map_err(ReconcileError::from))
+        .collect()
+}
+
+/// Compare `expected` with the accounts of `engine`. Mismatches are ordered by client,
+/// and by field in report column order within a client. Amounts are compared by value,
+/// so `1.0` matches `1.0000`.
+pub fn reconcile(
+    engine: &Engine,
+    expected: &[AccountSummary],
+) -> Result<Vec<Mismatch>, ReconcileError> {
+    let mut expected_by_client = BTreeMap::new();
+    for summary in expected {
+        if expected_by_client.insert(summary.client, summary).is_some() {
+            return Err(ReconcileError::DuplicateClient(summary.client));
+        }
+    }
+
+    let mut mismatches = Vec::new();
+    let actual = engine.account_summaries();
+    for summary in &actual {
+        let Some(expected) = expected_by_client.remove(&summary.client) else {
+            mismatches.push(Mismatch::ExtraClient(summary.client));
+            continue;
+        };
+        let fields = [
+            ("available", expected.available, summary.available),
+            ("held", expected.held, summary.held),
+            ("total", expected.total, summary.total),
+        ];
+        for (field, expected, actual) in fields {
+            if expected != actual {
+                mismatches.push(Mismatch::Field {
+                    client: summary.client,
+                    field,
+                    expected: expected.to_string(),
+                    actual: actual.to_string(),
+                });
+            }
+        }
+        if expected.locked != summary.locked {
+            mismatches.push(Mismatch::Field {
+                client: summary.client,
+                field: "locked",
+                expected: expected.locked.to_string(),
+                actual: summary.locked.to_string(),
+            });
+        }
+    }
+    mismatches.extend(expected_by_client.into_keys().map(Mismatch::MissingClient));
+    mismatches.sort_by_key(Mismatch::client);
+    Ok(mismatches)
+}
+
+/// Write mismatches as CSV with the columns `client, issue, expected, actual`.
+pub fn write_mismatches<W: Write>(mismatches: &[Mismatch], writer: &mut W) -> io::Result<()> {
+    writeln!(writer, "client, issue, expected, actual")?;
+    for mismatch in mismatches {
+        match mismatch {
+            Mismatch::Field {
+                client,
+                field,
+                expected,
+                actual,
+            } => writeln!(writer, "{client}, {field}, {expected}, {actual}")?,
+            Mismatch::MissingClient(client) => writeln!(writer, "{client}, missing, , ")?,
+            Mismatch::ExtraClient(client) => writeln!(writer, "{client}, extra, , ")?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::{Transaction, TransactionType};
+    use rust_decimal::Decimal;
+
+    fn engine() -> Engine {
+        let mut engine = Engine::new();
+        for (r#type, client, tx, amount) in [
+            (TransactionType::Deposit, 1, 1, Some(Decimal::new(150, 2))),
+            (TransactionType::Deposit, 2, 2, Some(Decimal::new(200, 2))),
+            (TransactionType::Dispute, 2, 2, None),
+            (TransactionType::Deposit, 4, 3, Some(Decimal::new(100, 2))),
+        ] {
+            engine
+                .apply_transaction(Transaction {
+                    r#type,
+                    client,
+                    tx,
+                    amount,
+                })
+                .unwrap();
+        }
+        engine
+    }
+
+    #[test]
+    fn test_matching_balances() {
+        let expected = read_expected(
+            "client, available, held, total, locked\n\
+             4, 1, 0, 1, false\n\
+             1, 1.5000, 0, 1.5, false\n\
+             2, 0, 2, 2, false\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(reconcile(&engine(), &expected).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_mismatches_missing_and_extra_clients() {
+        let expected = read_expected(
+            "client, available, held, total, locked\n\
+             1, 1.5, 0, 1.5, false\n\
+             2, 2, 0, 2, true\n\
+             3, 1, 0, 1, false\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        let mismatches = reconcile(&engine(), &expected).unwrap();
+
+        let mut output = Vec::new();
+        write_mismatches(&mismatches, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client, issue, expected, actual\n\
+             2, available, 2, 0.00\n\
+             2, held, 0, 2.00\n\
+             2, locked, true, false\n\
+             3, missing, , \n\
+             4, extra, , \n"
+        );
+    }
+
+    #[test]
+    fn test_duplicate_expected_client() {
+        let expected = read_expected(
+            "client, available, held, total, locked\n1, 1, 0, 1, false\n1, 1, 0, 1, false\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        assert!(matches!(
+            reconcile(&engine(), &expected),
+            Err(ReconcileError::DuplicateClient(1))
+        ));
+    }
+}
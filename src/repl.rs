@@ -0,0 +1,372 @@
+//! Interactive shell to inspect and change the state of an engine.
+
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use rustyline::DefaultEditor;
+use rustyline::error::ReadlineError;
+
+use crate::account::Account;
+use crate::dialect::CsvDialect;
+use crate::engine::Engine;
+use crate::history::{self, OutputFormat};
+use crate::metrics::Metrics;
+use crate::report::{self, Format};
+use crate::snapshot::Snapshot;
+use crate::transaction::{
+    Transaction, TransactionRecord, TransactionSource, parse_trimmed_headers,
+};
+
+const HELP: &str = "\
+Commands:
+  account <client>                      balances of a client's account
+  tx <tx>                               a recorded deposit or withdrawal
+  apply <type>,<client>,<tx>[,<amount>] apply a transaction, e.g. apply deposit,17,5000,10.0
+  undo                                  revert the last applied transaction
+  history <client>                      statement of a client's transactions
+  report                                account report of every client
+  help                                  this list
+  quit                                  leave the shell";
+
+/// Repl runs shell commands against an engine. Transactions applied with `apply` can be
+/// undone one by one, in reverse order.
+pub struct Repl {
+    engine: Engine,
+    // Whether the engine was restored from a snapshot, which keeps no history.
+    from_snapshot: bool,
+    // State from before each transaction applied in the shell, most recent last.
+    undo: Vec<Undo>,
+}
+
+/// Undo is what a transaction could have changed, saved right before applying it: the
+/// client's account, the record of its tx id, the journal length and the metrics.
+struct Undo {
+    transaction: Transaction,
+    account: Option<Account>,
+    record: Option<TransactionRecord>,
+    journal_len: usize,
+    metrics: Metrics,
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Repl {
+    /// Start with an empty engine.
+    pub fn new() -> Self {
+        Self {
+            engine: Engine::new().with_history().with_journal(),
+            from_snapshot: false,
+            undo: Vec::new(),
+        }
+    }
+
+    /// Start from the state saved in `snapshot`. Snapshots keep no history, so
+    /// `history` is not available.
+    pub fn from_snapshot(snapshot: Snapshot) -> Self {
+        Self {
+            engine: snapshot.restore_into(Engine::new().with_history().with_journal()),
+            from_snapshot: true,
+            undo: Vec::new(),
+        }
+    }
+
+    /// Apply every transaction of `source`. Loaded transactions cannot be undone.
+    pub fn load<T: TransactionSource>(&mut self, source: &mut T) {
+        self.engine.process_transactions(source);
+        self.undo.clear();
+    }
+
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    /// Run one command line and write its output. Returns `false` once the shell
+    /// should stop.
+    pub fn execute<W: Write + ?Sized>(&mut self, line: &str, out: &mut W) -> io::Result<bool> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(true);
+        }
+        let (command, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let args = args.trim();
+        match command {
+            "account" => match parse_id::<u16>(args, "client") {
+                Ok(client) => self.account(client, out)?,
+                Err(message) => writeln!(out, "{message}")?,
+            },
+            "tx" => match parse_id::<u32>(args, "tx") {
+                Ok(tx) => self.transaction(tx, out)?,
+                Err(message) => writeln!(out, "{message}")?,
+            },
+            "apply" => self.apply(args, out)?,
+            "undo" => self.undo(out)?,
+            "history" => match parse_id::<u16>(args, "client") {
+                Ok(client) => self.history(client, out)?,
+                Err(message) => writeln!(out, "{message}")?,
+            },
+            "report" => self.engine.write_report(out)?,
+            "help" => writeln!(out, "{HELP}")?,
+            "quit" | "exit" => return Ok(false),
+            _ => writeln!(out, "Unknown command '{command}', try 'help'.")?,
+        }
+        Ok(true)
+    }
+
+    /// Run the commands of a script, one per line, echoing each command before its
+    /// output. Blank lines and lines starting with `#` are skipped.
+    pub fn run_script<R: BufRead, W: Write + ?Sized>(
+        &mut self,
+        script: R,
+        out: &mut W,
+    ) -> io::Result<()> {
+        for line in script.lines() {
+            let line = line?;
+            if line.trim().is_empty() || line.trim().starts_with('#') {
+                continue;
+            }
+            writeln!(out, "> {}", line.trim())?;
+            if !self.execute(&line, out)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read commands from the terminal until `quit` or end of input. Commands are
+    /// kept in `history_file`, if given, across sessions.
+    pub fn run_interactive(&mut self, history_file: Option<&Path>) -> rustyline::Result<()> {
+        let mut editor = DefaultEditor::new()?;
+        if let Some(path) = history_file {
+            // A missing history file is normal on the first run.
+            let _ = editor.load_history(path);
+        }
+        println!("Type 'help' for the list of commands.");
+        let mut out = io::stdout();
+        loop {
+            let line = match editor.readline("> ") {
+                Ok(line) => line,
+                Err(ReadlineError::Interrupted) => continue,
+                Err(ReadlineError::Eof) => break,
+                Err(e) => return Err(e),
+            };
+            if !line.trim().is_empty() {
+                editor.add_history_entry(line.as_str())?;
+            }
+            if !self.execute(&line, &mut out)? {
+                break;
+            }
+            out.flush()?;
+        }
+        if let Some(path) = history_file {
+            editor.save_history(path)?;
+        }
+        Ok(())
+    }
+
+    fn account<W: Write + ?Sized>(&self, client: u16, out: &mut W) -> io::Result<()> {
+        match self.engine.accounts.get(&client) {
+            Some(account) => report::write_account_summaries(&[account.into()], Format::Csv, out),
+            None => writeln!(out, "Client {client} not found."),
+        }
+    }
+
+    fn transaction<W: Write + ?Sized>(&self, tx: u32, out: &mut W) -> io::Result<()> {
+        let Some(record) = self.engine.transactions.get(&tx) else {
+            return writeln!(out, "Transaction {tx} not found.");
+        };
+        let transaction = &record.transaction;
+        writeln!(
+            out,
+            "tx {}: {} of {} for client {}, dispute state {}",
+            tx,
+            transaction.r#type.name(),
+            transaction.amount.unwrap_or_default(),
+            transaction.client,
+            record.dispute_state.name()
+        )
+    }
+
+    fn apply<W: Write + ?Sized>(&mut self, args: &str, out: &mut W) -> io::Result<()> {
+        let parser = match CsvDialect::default()
+            .row_parser(&parse_trimmed_headers("type, client, tx, amount"))
+        {
+            Ok(parser) => parser,
+            Err(e) => return writeln!(out, "{e}"),
+        };
+        let transaction = match parser.parse(&parse_trimmed_headers(args)) {
+            Ok(transaction) => transaction,
+            Err(e) => return writeln!(out, "{e} Expected <type>,<client>,<tx>[,<amount>]."),
+        };
+        let Transaction {
+            r#type, client, tx, ..
+        } = transaction;
+        let undo = Undo {
+            transaction: transaction.clone(),
+            account: self.engine.accounts.get(&client).cloned(),
+            record: self.engine.transactions.get(&tx).cloned(),
+            journal_len: self.engine.ledger.journal().len(),
+            metrics: self.engine.metrics.clone(),
+        };
+        match self.engine.apply_transaction(transaction) {
+            Ok(()) => {
+                self.undo.push(undo);
+                writeln!(
+                    out,
+                    "Applied {} {} for client {}.",
+                    r#type.name(),
+                    tx,
+                    client
+                )
+            }
+            Err(e) => writeln!(out, "Rejected ({}): {}", e.code(), e),
+        }
+    }
+
+    /// Put back the state saved before the last transaction applied in the shell.
+    fn undo<W: Write + ?Sized>(&mut self, out: &mut W) -> io::Result<()> {
+        let Some(undo) = self.undo.pop() else {
+            return writeln!(out, "Nothing to undo.");
+        };
+        let Transaction {
+            r#type, client, tx, ..
+        } = undo.transaction;
+        match undo.account {
+            Some(account) => self.engine.accounts.insert(client, account),
+            None => self.engine.accounts.remove(&client),
+        };
+        match undo.record {
+            Some(record) => self.engine.transactions.insert(tx, record),
+            None => self.engine.transactions.remove(&tx),
+        };
+        self.engine.ledger.truncate(undo.journal_len);
+        self.engine.metrics = undo.metrics;
+        if let Some(history) = &mut self.engine.history {
+            history.pop();
+        }
+        writeln!(out, "Undid {} {} for client {}.", r#type.name(), tx, client)
+    }
+
+    fn history<W: Write + ?Sized>(&self, client: u16, out: &mut W) -> io::Result<()> {
+        if self.from_snapshot {
+            return writeln!(out, "Snapshots keep no transaction history.");
+        }
+        match history::client_statement(&self.engine, client, ..) {
+            Ok(statement) => statement.write(OutputFormat::Text, out),
+            Err(e) => writeln!(out, "{e}"),
+        }
+    }
+}
+
+fn parse_id<T: std::str::FromStr>(args: &str, name: &str) -> Result<T, String> {
+    args.parse()
+        .map_err(|_| format!("Expected a {name} id, got '{args}'."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::CsvTransactionSource;
+
+    fn run(repl: &mut Repl, script: &str) -> String {
+        let mut output = Vec::new();
+        repl.run_script(script.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_script() {
+        let mut repl = Repl::new();
+        repl.load(&mut CsvTransactionSource::from_reader(
+            "type, client, tx, amount\ndeposit, 17, 1001, 5.0\n".as_bytes(),
+        ));
+
+        let output = run(
+            &mut repl,
+            "# support session\n\
+             account 17\n\
+             tx 1001\n\
+             apply deposit,17,5000,10.0\n\
+             apply withdrawal, 17, 5001, 100\n\
+             apply dispute,17,1001\n\
+             account 17\n\
+             undo\n\
+             undo\n\
+             undo\n\
+             account 17\n\
+             account x\n\
+             frobnicate\n\
+             quit\n\
+             report\n",
+        );
+        assert_eq!(
+            output,
+            "> account 17\n\
+             client, available, held, total, locked\n\
+             17, 5.0, 0, 5.0, false\n\
+             > tx 1001\n\
+             tx 1001: deposit of 5.0 for client 17, dispute state none\n\
+             > apply deposit,17,5000,10.0\n\
+             Applied deposit 5000 for client 17.\n\
+             > apply withdrawal, 17, 5001, 100\n\
+             Rejected (insufficient_funds): Insufficient funds for client 17.\n\
+             > apply dispute,17,1001\n\
+             Applied dispute 1001 for client 17.\n\
+             > account 17\n\
+             client, available, held, total, locked\n\
+             17, 10.0, 5.0, 15.0, false\n\
+             > undo\n\
+             Undid dispute 1001 for client 17.\n\
+             > undo\n\
+             Undid deposit 5000 for client 17.\n\
+             > undo\n\
+             Nothing to undo.\n\
+             > account 17\n\
+             client, available, held, total, locked\n\
+             17, 5.0, 0, 5.0, false\n\
+             > account x\n\
+             Expected a client id, got 'x'.\n\
+             > frobnicate\n\
+             Unknown command 'frobnicate', try 'help'.\n\
+             > quit\n"
+        );
+        let engine = repl.engine();
+        assert!(!engine.transactions.contains_key(&5000));
+        // Undo also reverts the ledger and the metrics, not just the accounts.
+        assert_eq!(engine.ledger.journal().len(), 1);
+        assert_eq!(engine.metrics.open_disputes(), 0);
+        assert_eq!(engine.history.as_ref().map(Vec::len), Some(1));
+    }
+
+    #[test]
+    fn test_history_and_snapshot() {
+        let mut repl = Repl::new();
+        run(
+            &mut repl,
+            "apply deposit,1,1,2.0\napply withdrawal,1,2,0.5\n",
+        );
+        let output = run(&mut repl, "history 1");
+        assert!(output.contains("Statement for client 1"), "{output}");
+        assert!(output.contains("withdrawal"), "{output}");
+
+        let mut repl = Repl::from_snapshot(Snapshot::from_engine(repl.engine()));
+        assert_eq!(
+            run(
+                &mut repl,
+                "apply withdrawal,1,3,1.5\nundo\nhistory 1\naccount 1\n"
+            ),
+            "> apply withdrawal,1,3,1.5\n\
+             Applied withdrawal 3 for client 1.\n\
+             > undo\n\
+             Undid withdrawal 3 for client 1.\n\
+             > history 1\n\
+             Snapshots keep no transaction history.\n\
+             > account 1\n\
+             client, available, held, total, locked\n\
+             1, 1.5, 0, 1.5, false\n"
+        );
+    }
+}
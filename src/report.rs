@@ -0,0 +1,158 @@
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::account::AccountSummary;
+use crate::engine::Engine;
+
+/// Format is a file format for transaction input and account reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    JsonLines,
+    /// The fixed-size records of the `binary` module. Only used for transactions.
+    Binary,
+    /// OFX bank statements, only read as transactions with a client mapping.
+    Ofx,
+    /// ISO 20022 camt.053 bank statements, only read as transactions with a client
+    /// mapping.
+    Camt053,
+}
+
+impl Format {
+    /// Detect the format from a file extension, if it is a known one.
+    /// Compression extensions are skipped, so `input.jsonl.gz` is JSON Lines.
+    pub fn from_path(path: &str) -> Option<Self> {
+        let mut path = Path::new(path);
+        if matches!(
+            path.extension().and_then(|e| e.to_str()),
+            Some("gz" | "zst")
+        ) {
+            path = Path::new(path.file_stem()?);
+        }
+        path.extension()?.to_str()?.parse().ok()
+    }
+}
+
+impl FromStr for Format {
+    type Err = FormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(Format::Csv),
+            "jsonl" | "ndjson" => Ok(Format::JsonLines),
+            "bin" | "binary" | "txb" => Ok(Format::Binary),
+            "ofx" | "qfx" => Ok(Format::Ofx),
+            "camt" | "camt053" => Ok(Format::Camt053),
+            _ => Err(FormatError::Unknown(s.to_string())),
+        }
+    }
+}
+
+/// FormatError represents an unrecognised format name.
+#[derive(Debug, Error)]
+pub enum FormatError {
+    #[error("Unknown format '{0}'. Expected csv, jsonl, binary, ofx or camt.")]
+    Unknown(String),
+}
+
+/// Write the account report for `engine` in the given format.
+pub fn write_report<W: Write + ?Sized>(
+    engine: &Engine,
+    format: Format,
+    writer: &mut W,
+) -> io::Result<()> {
+    write_account_summaries(&engine.account_summaries(), format, writer)
+}
+
+/// Write an account report of `summaries`, e.g. the accounts of a saved snapshot.
+pub fn write_account_summaries<W: Write + ?Sized>(
+    summaries: &[AccountSummary],
+    format: Format,
+    writer: &mut W,
+) -> io::Result<()> {
+    match format {
+        Format::Csv => {
+            writeln!(writer, "client, available, held, total, locked")?;
+            for summary in summaries {
+                writeln!(
+                    writer,
+                    "{}, {}, {}, {}, {}",
+                    summary.client, summary.available, summary.held, summary.total, summary.locked
+                )?;
+            }
+        }
+        Format::JsonLines => {
+            for summary in summaries {
+                serde_json::to_writer(&mut *writer, summary)?;
+                writeln!(writer)?;
+            }
+        }
+        Format::Binary => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Reports cannot be written in the binary format.",
+            ));
+        }
+        Format::Ofx | Format::Camt053 => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Reports cannot be written as bank statements.",
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::{Transaction, TransactionType};
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn test_format_detection() {
+        assert_eq!(Format::from_path("input.csv"), Some(Format::Csv));
+        assert_eq!(
+            Format::from_path("/tmp/input.JSONL"),
+            Some(Format::JsonLines)
+        );
+        assert_eq!(Format::from_path("input.ndjson"), Some(Format::JsonLines));
+        assert_eq!(
+            Format::from_path("input.jsonl.zst"),
+            Some(Format::JsonLines)
+        );
+        assert_eq!(Format::from_path("input.csv.gz"), Some(Format::Csv));
+        assert_eq!(Format::from_path("input.txb"), Some(Format::Binary));
+        assert_eq!(Format::from_path("statement.OFX"), Some(Format::Ofx));
+        assert_eq!("camt".parse::<Format>().ok(), Some(Format::Camt053));
+        assert_eq!(Format::from_path("input"), None);
+        assert_eq!(Format::from_path("-"), None);
+        assert!("xml".parse::<Format>().is_err());
+    }
+
+    #[test]
+    fn test_write_report_as_json_lines() {
+        let mut engine = Engine::new();
+        for (client, tx) in [(2, 1), (1, 2)] {
+            engine
+                .apply_transaction(Transaction {
+                    r#type: TransactionType::Deposit,
+                    client,
+                    tx,
+                    amount: Some(Decimal::new(150, 2)),
+                })
+                .unwrap();
+        }
+
+        let mut output = Vec::new();
+        write_report(&engine, Format::JsonLines, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"client\":1,\"available\":\"1.50\",\"held\":\"0\",\"total\":\"1.50\",\"locked\":false}\n\
+             {\"client\":2,\"available\":\"1.50\",\"held\":\"0\",\"total\":\"1.50\",\"locked\":false}\n"
+        );
+    }
+}
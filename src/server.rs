@@ -0,0 +1,259 @@
+//! TCP server mode. Each connection streams CSV rows into a shared `Engine`.
+//!
+//! The protocol is line based. The first row a client sends is the CSV header,
+//! every following row is a transaction and is answered with one line:
+//!
+//! ```text
+//! > type, client, tx, amount
+//! > deposit, 1, 1, 1.0
+//! < OK 1
+//! > withdrawal, 1, 2, 5.0
+//! < REJECTED 2 Insufficient funds for client 1.
+//! > deposit, 1
+//! < ERROR Invalid tx ''.
+//! > REPORT
+//! < client, available, held, total, locked
+//! < 1, 1.0, 0, 1.0, false
+//! <
+//! ```
+//!
+//! `REPORT` can be sent at any time and is answered with the current account report
+//! followed by an empty line. A header without the required columns, or a line longer
+//! than `MAX_LINE_LENGTH` bytes, is answered with `ERROR` and the connection is closed.
+
+use std::sync::{Arc, Mutex};
+
+use log::{info, warn};
+use tokio::io::{
+    self, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, BufWriter,
+};
+use tokio::net::TcpListener;
+use tracing::{Instrument, info_span};
+
+use crate::engine::Engine;
+use crate::transaction::LineParser;
+
+/// Command a client sends to request the current account report.
+pub const REPORT_COMMAND: &str = "REPORT";
+
+/// Longest line, in bytes without the newline, a client may send.
+pub const MAX_LINE_LENGTH: usize = 4096;
+
+/// SharedEngine is the engine shared by all connections.
+pub type SharedEngine = Arc<Mutex<Engine>>;
+
+/// Accept connections on `listener` until the task is cancelled, handling each
+/// connection on its own tokio task.
+pub async fn serve(listener: TcpListener, engine: SharedEngine) -> io::Result<()> {
+    info!("Listening on {}", listener.local_addr()?);
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                // e.g. out of file descriptors; keep serving existing connections.
+                warn!("Failed to accept connection: {}", e);
+                continue;
+            }
+        };
+        let engine = Arc::clone(&engine);
+        let span = info_span!("source", %peer);
+        tokio::spawn(
+            async move {
+                info!("Connection from {} opened.", peer);
+                if let Err(e) = handle_connection(stream, engine).await {
+                    warn!("Connection from {} failed: {}", peer, e);
+                }
+                info!("Connection from {} closed.", peer);
+            }
+            .instrument(span),
+        );
+    }
+}
+
+/// Run the line protocol for one connection until the client closes it.
+pub async fn handle_connection<S>(stream: S, engine: SharedEngine) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (reader, writer) = io::split(stream);
+    let mut reader = BufReader::new(reader);
+    let mut writer = BufWriter::new(writer);
+    let mut parser: Option<LineParser> = None;
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        // Read at most one byte past the limit, so an overlong line is detected without
+        // buffering it.
+        let limit = MAX_LINE_LENGTH as u64 + 2;
+        if (&mut reader).take(limit).read_line(&mut line).await? == 0 {
+            break;
+        }
+        if line.trim_end_matches(['\r', '\n']).len() > MAX_LINE_LENGTH {
+            let error = format!("ERROR Line longer than {MAX_LINE_LENGTH} bytes.\n");
+            writer.write_all(error.as_bytes()).await?;
+            break;
+        }
+        let row = line.trim();
+        if row.is_empty() {
+            continue;
+        }
+
+        if row.eq_ignore_ascii_case(REPORT_COMMAND) {
+            let mut report = Vec::new();
+            engine
+                .lock()
+                .expect("Engine lock poisoned")
+                .write_report(&mut report)?;
+            writer.write_all(&report).await?;
+            writer.write_all(b"\n").await?;
+        } else if let Some(parser) = &mut parser {
+            let response = match parser.parse(row) {
+                Ok(transaction) => {
+                    let tx = transaction.tx;
+                    match engine
+                        .lock()
+                        .expect("Engine lock poisoned")
+                        .apply_transaction(transaction)
+                    {
+                        Ok(()) => format!("OK {tx}\n"),
+                        Err(e) => format!("REJECTED {tx} {e}\n"),
+                    }
+                }
+                Err(e) => format!("ERROR {e}\n"),
+            };
+            writer.write_all(response.as_bytes()).await?;
+        } else {
+            match LineParser::new(row) {
+                Ok(header_parser) => parser = Some(header_parser),
+                Err(e) => {
+                    writer.write_all(format!("ERROR {e}\n").as_bytes()).await?;
+                    break;
+                }
+            }
+        }
+
+        // Only flush once the client has no more rows in flight, so pipelined
+        // rows are acknowledged in batches.
+        if reader.buffer().is_empty() {
+            writer.flush().await?;
+        }
+    }
+    writer.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+    use std::net::SocketAddr;
+    use tokio::net::TcpStream;
+
+    async fn start_server() -> (SocketAddr, SharedEngine) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let engine = SharedEngine::default();
+        tokio::spawn(serve(listener, Arc::clone(&engine)));
+        (addr, engine)
+    }
+
+    async fn send(addr: SocketAddr, input: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(input.as_bytes()).await.unwrap();
+        stream.shutdown().await.unwrap();
+
+        let mut output = String::new();
+        tokio::io::AsyncReadExt::read_to_string(&mut stream, &mut output)
+            .await
+            .unwrap();
+        output
+    }
+
+    #[tokio::test]
+    async fn test_acknowledges_and_rejects_rows() {
+        let (addr, _engine) = start_server().await;
+        let output = send(
+            addr,
+            "type, client, tx, amount\n\
+             deposit, 1, 1, 1.0\n\
+             withdrawal, 1, 2, 5.0\n\
+             deposit, 1\n\
+             REPORT\n",
+        )
+        .await;
+
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "OK 1");
+        assert_eq!(lines[1], "REJECTED 2 Insufficient funds for client 1.");
+        assert_eq!(lines[2], "ERROR Invalid tx ''.");
+        assert_eq!(
+            lines[3..],
+            [
+                "client, available, held, total, locked",
+                "1, 1.0, 0, 1.0, false",
+                ""
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_closes_on_invalid_header_or_long_line() {
+        let (addr, _engine) = start_server().await;
+        let output = send(addr, "type, client, amount\ndeposit, 1, 1.0\n").await;
+        assert_eq!(output, "ERROR Missing column 'tx'.\n");
+
+        let long_row = format!("deposit, 1, 1, 1.{}\n", "0".repeat(MAX_LINE_LENGTH));
+        let output = send(
+            addr,
+            &format!("type, client, tx, amount\n{long_row}deposit, 1, 2, 1.0\n"),
+        )
+        .await;
+        assert_eq!(
+            output,
+            format!("ERROR Line longer than {MAX_LINE_LENGTH} bytes.\n")
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_concurrent_connections_share_engine() {
+        let (addr, engine) = start_server().await;
+
+        let connections: Vec<_> = (1..=200u16)
+            .map(|client| {
+                tokio::spawn(async move {
+                    let base = u32::from(client) * 10;
+                    let input = format!(
+                        "type,client,tx,amount\n\
+                         deposit,{client},{},10.0\n\
+                         withdrawal,{client},{},4.0\n\
+                         dispute,{client},{},\n",
+                        base,
+                        base + 1,
+                        base + 1,
+                    );
+                    send(addr, &input).await
+                })
+            })
+            .collect();
+
+        for (client, connection) in (1..=200u32).zip(connections) {
+            let output = connection.await.unwrap();
+            assert_eq!(
+                output,
+                format!(
+                    "OK {}\nOK {}\nOK {}\n",
+                    client * 10,
+                    client * 10 + 1,
+                    client * 10 + 1
+                )
+            );
+        }
+
+        let engine = engine.lock().unwrap();
+        assert_eq!(engine.accounts.len(), 200);
+        for account in engine.accounts.values() {
+            assert_eq!(account.total(), Decimal::new(60, 1));
+            assert_eq!(account.held(), Decimal::new(40, 1));
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
+use std::thread::{self, JoinHandle};
+
+use crate::engine::Engine;
+use crate::transaction::{Transaction, TransactionSource, TransactionType};
+
+/// Maximum number of shards. Tx-id ownership is tracked as a bitmask per tx id.
+pub const MAX_SHARDS: usize = 64;
+
+// Number of batches queued per shard before the router blocks.
+const CHANNEL_CAPACITY: usize = 64;
+// Transactions are sent to workers in batches to keep channel overhead low.
+const BATCH_SIZE: usize = 1024;
+
+/// ShardMessage is what the router sends to a worker engine.
+enum ShardMessage {
+    Apply(Vec<Transaction>),
+    // Ask a shard whether it has recorded the given tx id.
+    Contains(u32, Sender<bool>),
+}
+
+/// ShardedEngine routes transactions by client to N worker engines, each running on
+/// its own thread. Transactions for the same client always go to the same worker, so
+/// per-client order is preserved.
+///
+/// Tx ids share one namespace across all clients. The router remembers which shards
+/// have seen a deposit or withdrawal for each tx id, and only when the same id shows up
+/// for a client on a different shard does it ask those shards whether the id was
+/// recorded. Such rows are sent to the shard that recorded the id, which keeps
+/// duplicate and wrong-client rejections identical to a single `Engine`.
+pub struct ShardedEngine {
+    senders: Vec<SyncSender<ShardMessage>>,
+    // Transactions routed to each shard but not yet sent.
+    batches: Vec<Vec<Transaction>>,
+    workers: Vec<JoinHandle<Engine>>,
+    // Bitmask of shards that have been sent a deposit or withdrawal for a tx id.
+    tx_shards: HashMap<u32, u64>,
+}
+
+impl ShardedEngine {
+    /// Create a sharded engine with `shards` worker threads.
+    /// Panics if `shards` is zero or greater than `MAX_SHARDS`.
+    pub fn new(shards: usize) -> Self {
+        assert!(
+            (1..=MAX_SHARDS).contains(&shards),
+            "Shard count must be between 1 and {MAX_SHARDS}"
+        );
+
+        let mut senders = Vec::with_capacity(shards);
+        let mut workers = Vec::with_capacity(shards);
+        for _ in 0..shards {
+            let (sender, receiver) = mpsc::sync_channel(CHANNEL_CAPACITY);
+            senders.push(sender);
+            workers.push(thread::spawn(move || run_worker(receiver)));
+        }
+
+        Self {
+            batches: (0..shards)
+                .map(|_| Vec::with_capacity(BATCH_SIZE))
+                .collect(),
+            senders,
+            workers,
+            tx_shards: HashMap::new(),
+        }
+    }
+
+    pub fn process_transactions<T: TransactionSource>(&mut self, source: &mut T) {
+        for transaction in source.transactions() {
+            self.apply_transaction(transaction);
+        }
+    }
+
+    /// Route `transaction` to a worker. Rejections are counted in the metrics of the
+    /// merged engine returned by `finish`.
+    pub fn apply_transaction(&mut self, transaction: Transaction) {
+        let own = self.shard_for(transaction.client);
+        let shard = self.recording_shard(transaction.tx, own).unwrap_or(own);
+        if matches!(
+            transaction.r#type,
+            TransactionType::Deposit | TransactionType::Withdrawal
+        ) {
+            *self.tx_shards.entry(transaction.tx).or_insert(0) |= 1u64 << shard;
+        }
+
+        self.batches[shard].push(transaction);
+        if self.batches[shard].len() >= BATCH_SIZE {
+            self.flush(shard);
+        }
+    }
+
+    /// Wait for all workers to drain their queues and merge their state into one `Engine`.
+    pub fn finish(mut self) -> Engine {
+        for shard in 0..self.senders.len() {
+            self.flush(shard);
+        }
+        drop(self.senders);
+
+        let mut merged = Engine::new();
+        for worker in self.workers {
+            let engine = worker.join().expect("Shard worker panicked");
+            merged.accounts.extend(engine.accounts);
+            merged.transactions.extend(engine.transactions);
+            merged.ledger.merge(engine.ledger);
+            merged.metrics.merge(&engine.metrics);
+        }
+        merged
+    }
+
+    fn shard_for(&self, client: u16) -> usize {
+        client as usize % self.senders.len()
+    }
+
+    fn flush(&mut self, shard: usize) {
+        if self.batches[shard].is_empty() {
+            return;
+        }
+        let batch = std::mem::replace(&mut self.batches[shard], Vec::with_capacity(BATCH_SIZE));
+        self.senders[shard]
+            .send(ShardMessage::Apply(batch))
+            .expect("Shard worker stopped unexpectedly");
+    }
+
+    // The shard other than `own` that recorded `tx`, if any. Rows naming a tx id
+    // recorded on another shard go there, so that its engine rejects them as a duplicate
+    // or wrong client exactly like a single `Engine` would. Pending batches are flushed
+    // before asking and channels are FIFO, so the answer reflects every transaction
+    // routed to that shard before this call.
+    fn recording_shard(&mut self, tx: u32, own: usize) -> Option<usize> {
+        let others = self.tx_shards.get(&tx).copied().unwrap_or(0) & !(1u64 << own);
+        if others == 0 {
+            return None;
+        }
+        (0..self.senders.len())
+            .filter(|shard| others & (1u64 << shard) != 0)
+            .find(|&shard| {
+                self.flush(shard);
+                let (reply, answer) = mpsc::channel();
+                self.senders[shard]
+                    .send(ShardMessage::Contains(tx, reply))
+                    .expect("Shard worker stopped unexpectedly");
+                answer.recv().expect("Shard worker stopped unexpectedly")
+            })
+    }
+}
+
+fn run_worker(receiver: Receiver<ShardMessage>) -> Engine {
+    let mut engine = Engine::new();
+    for message in receiver {
+        match message {
+            ShardMessage::Apply(batch) => {
+                for transaction in batch {
+                    let _ = engine.apply_transaction(transaction);
+                }
+            }
+            ShardMessage::Contains(tx, reply) => {
+                // The router may have given up waiting; nothing to do then.
+                let _ = reply.send(engine.transactions.contains_key(&tx));
+            }
+        }
+    }
+    engine
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::tx;
+    use rust_decimal::Decimal;
+
+    fn report(engine: &Engine) -> String {
+        let mut output = Vec::new();
+        engine.write_report(&mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    fn run_both(transactions: &[Transaction], shards: usize) -> (Engine, Engine) {
+        let mut single = Engine::new();
+        let mut sharded = ShardedEngine::new(shards);
+        for transaction in transactions {
+            let _ = single.apply_transaction(transaction.clone());
+            sharded.apply_transaction(transaction.clone());
+        }
+        (single, sharded.finish())
+    }
+
+    #[test]
+    fn test_sharded_report_matches_single_engine() {
+        use TransactionType::*;
+        let transactions = vec![
+            tx(Deposit, 1, 1, Some(1000)),
+            tx(Deposit, 2, 2, Some(2000)),
+            tx(Deposit, 3, 3, Some(3000)),
+            tx(Withdrawal, 1, 4, Some(500)),
+            tx(Dispute, 2, 2, None),
+            tx(Chargeback, 2, 2, None),
+            tx(Deposit, 2, 5, Some(100)),
+            tx(Dispute, 3, 3, None),
+            tx(Resolve, 3, 3, None),
+            tx(Withdrawal, 4, 6, Some(100)),
+        ];
+
+        for shards in 1..=4 {
+            let (single, sharded) = run_both(&transactions, shards);
+            assert_eq!(report(&single), report(&sharded), "shards = {shards}");
+            assert_eq!(
+                single.ledger.trial_balance(),
+                sharded.ledger.trial_balance()
+            );
+            assert_eq!(sharded.ledger.trial_balance().total(), Decimal::ZERO);
+        }
+    }
+
+    #[test]
+    fn test_sharded_duplicate_tx_id_across_shards() {
+        use TransactionType::*;
+        let transactions = vec![
+            // Client 2 is locked, so its deposit with tx 10 is never recorded and
+            // client 1 may use tx 10 afterwards.
+            tx(Deposit, 2, 1, Some(100)),
+            tx(Dispute, 2, 1, None),
+            tx(Chargeback, 2, 1, None),
+            tx(Deposit, 2, 10, Some(100)),
+            tx(Deposit, 1, 10, Some(700)),
+            // Tx 10 is now taken, client 3 must be rejected.
+            tx(Deposit, 3, 10, Some(900)),
+            // Disputes only apply to the owner of the transaction.
+            tx(Dispute, 3, 10, None),
+            tx(Dispute, 1, 10, None),
+        ];
+
+        for shards in 1..=4 {
+            let (single, sharded) = run_both(&transactions, shards);
+            assert_eq!(report(&single), report(&sharded), "shards = {shards}");
+            for code in [
+                "duplicate_transaction",
+                "wrong_client",
+                "transaction_not_found",
+            ] {
+                assert_eq!(
+                    sharded.metrics.rejected(code),
+                    single.metrics.rejected(code),
+                    "{code}, shards = {shards}"
+                );
+            }
+            assert_eq!(sharded.metrics.rejected("duplicate_transaction"), 1);
+            assert_eq!(sharded.metrics.rejected("wrong_client"), 1);
+
+            let account1 = &sharded.accounts[&1];
+            assert_eq!(account1.held(), Decimal::new(700, 2));
+            assert!(!sharded.accounts.contains_key(&3));
+        }
+    }
+}
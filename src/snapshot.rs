@@ -0,0 +1,145 @@
+//! Saved engine state: the accounts and recorded transactions of a run, without the
+//! history. Snapshots are written by `process --snapshot`, loaded by `report`, `diff`
+//! and the shell, and checkpoint the inbox watcher.
+
+use std::io::{Read, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::account::{Account, AccountSummary};
+use crate::engine::Engine;
+use crate::ledger::LedgerAccount;
+use crate::metrics::Metrics;
+use crate::transaction::TransactionRecord;
+
+/// Snapshot is the state of an engine run that can be saved as JSON and compared later.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Accounts ordered by client id.
+    pub accounts: Vec<AccountSummary>,
+    /// Recorded deposits and withdrawals ordered by tx id.
+    pub transactions: Vec<TransactionRecord>,
+}
+
+impl Snapshot {
+    pub fn from_engine(engine: &Engine) -> Self {
+        let mut transactions: Vec<TransactionRecord> =
+            engine.transactions.values().cloned().collect();
+        transactions.sort_by_key(|record| record.transaction.tx);
+        Self {
+            accounts: engine.account_summaries(),
+            transactions,
+        }
+    }
+
+    /// Build an engine in the saved state, without history or journal.
+    pub fn restore(&self) -> Engine {
+        self.restore_into(Engine::new())
+    }
+
+    /// Put the saved state into an empty `engine`, e.g. one that keeps a journal. The
+    /// ledger starts with an opening balance entry for every non-zero client ledger
+    /// account, and the metric gauges start from the saved disputes, locks and held
+    /// funds.
+    pub fn restore_into(&self, mut engine: Engine) -> Engine {
+        for summary in &self.accounts {
+            let client = summary.client;
+            for (account, amount) in [
+                (LedgerAccount::ClientAvailable(client), summary.available),
+                (LedgerAccount::ClientHeld(client), summary.held),
+            ] {
+                if !amount.is_zero() {
+                    engine.ledger.open(account, amount);
+                }
+            }
+            let mut account = Account::new(client);
+            account.refresh(&engine.ledger);
+            account.is_locked = summary.locked;
+            engine.accounts.insert(client, account);
+        }
+        for record in &self.transactions {
+            engine
+                .transactions
+                .insert(record.transaction.tx, record.clone());
+        }
+        let mut metrics = Metrics::new();
+        metrics.sync_gauges(&engine);
+        engine.metrics = metrics;
+        engine
+    }
+
+    pub fn read_json<R: Read>(reader: R) -> serde_json::Result<Self> {
+        serde_json::from_reader(reader)
+    }
+
+    pub fn write_json<W: Write>(&self, writer: W) -> serde_json::Result<()> {
+        serde_json::to_writer_pretty(writer, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use super::*;
+    use crate::diff::diff;
+    use crate::ledger::EntryReference;
+    use crate::test_support::{apply_all, tx};
+    use crate::transaction::{Transaction, TransactionType};
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let snapshot = Snapshot::from_engine(&apply_all(
+            Engine::new(),
+            [
+                tx(TransactionType::Deposit, 1, 1, Some(150)),
+                tx(TransactionType::Dispute, 1, 1, None),
+            ],
+        ));
+        let mut json = Vec::new();
+        snapshot.write_json(&mut json).unwrap();
+        let loaded = Snapshot::read_json(json.as_slice()).unwrap();
+
+        assert!(diff(&snapshot, &loaded).is_empty());
+        assert_eq!(loaded.accounts, snapshot.accounts);
+    }
+
+    #[test]
+    fn test_restore() {
+        use TransactionType::*;
+        let snapshot = Snapshot::from_engine(&apply_all(
+            Engine::new(),
+            [
+                tx(Deposit, 1, 1, Some(500)),
+                tx(Deposit, 1, 2, Some(300)),
+                tx(Dispute, 1, 1, None),
+                tx(Deposit, 2, 3, Some(100)),
+                tx(Withdrawal, 2, 4, Some(100)),
+            ],
+        ));
+        let mut engine = snapshot.restore_into(Engine::new().with_journal());
+        assert!(diff(&snapshot, &Snapshot::from_engine(&engine)).is_empty());
+        assert_eq!(engine.ledger.trial_balance().total(), Decimal::ZERO);
+        // Client 1 has available and held funds, client 2 nothing to open.
+        assert_eq!(engine.ledger.journal().len(), 2);
+        assert!(
+            engine
+                .ledger
+                .journal()
+                .iter()
+                .all(|entry| entry.reference == EntryReference::OpeningBalance)
+        );
+
+        for (r#type, tx, amount) in [(Resolve, 1, None), (Withdrawal, 5, Some(800))] {
+            engine
+                .apply_transaction(Transaction {
+                    r#type,
+                    client: 1,
+                    tx,
+                    amount: amount.map(|a| Decimal::new(a, 2)),
+                })
+                .unwrap();
+        }
+        assert_eq!(engine.accounts[&1].total(), Decimal::ZERO);
+    }
+}
@@ -0,0 +1,644 @@
+//! Importers for bank statements in OFX and ISO 20022 camt.053 format.
+//!
+//! Statement entries become deposits (credits) and withdrawals (debits). The client of
+//! each entry is looked up from the statement's account reference, e.g. an IBAN, with a
+//! `ClientMapping`. The entry reference (OFX `FITID`, camt `AcctSvcrRef` or `NtryRef`)
+//! is used as the tx id when it is a number, otherwise a stable 32-bit hash of it is used.
+//! A statement in which two different references map to the same tx id is rejected as a
+//! whole rather than having the engine drop the later entries as duplicates.
+
+use std::collections::HashMap;
+use std::io::{BufRead, Read};
+use std::path::Path;
+use std::str::FromStr;
+
+use log::warn;
+use quick_xml::events::Event;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::input::Input;
+use crate::transaction::{MalformedRows, Transaction, TransactionSource, TransactionType};
+
+/// StatementEntry is one booked entry of a bank statement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatementEntry {
+    /// Reference of the statement account, e.g. an IBAN or bank account number.
+    pub account: String,
+    /// Bank reference of the entry.
+    pub reference: String,
+    /// Signed amount, positive for credits and negative for debits.
+    pub amount: Decimal,
+}
+
+impl StatementEntry {
+    /// The tx id for this entry, see the module documentation.
+    pub fn tx_id(&self) -> u32 {
+        self.reference
+            .parse()
+            .unwrap_or_else(|_| fnv1a(&self.reference))
+    }
+}
+
+// 32-bit FNV-1a, stable across runs and platforms.
+fn fnv1a(value: &str) -> u32 {
+    value.bytes().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// ClientMapping maps statement account references to client ids. References are
+/// compared without whitespace and case, so `DE89 3704 ...` matches `de893704...`.
+///
+/// It can be loaded from TOML:
+///
+/// ```toml
+/// default_client = 99
+/// # Decimal separator of OFX amounts, detected per amount when not set.
+/// decimal_separator = ","
+///
+/// [accounts]
+/// "DE89370400440532013000" = 1
+/// "1234567890" = 2
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClientMapping {
+    accounts: HashMap<String, u16>,
+    /// Client for accounts that are not listed. Entries of unlisted accounts are
+    /// skipped when this is not set.
+    default_client: Option<u16>,
+    /// Decimal separator of OFX amounts, the other of `.` and `,` groups thousands.
+    decimal_separator: Option<char>,
+}
+
+impl ClientMapping {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load a mapping from a TOML file.
+    pub fn from_toml_file(path: impl AsRef<Path>) -> Result<Self, StatementError> {
+        std::fs::read_to_string(path)?.parse()
+    }
+
+    pub fn with_account(mut self, account: &str, client: u16) -> Self {
+        self.accounts.insert(normalize_account(account), client);
+        self
+    }
+
+    pub fn with_default_client(mut self, client: u16) -> Self {
+        self.default_client = Some(client);
+        self
+    }
+
+    pub fn with_decimal_separator(mut self, separator: char) -> Self {
+        self.decimal_separator = Some(separator);
+        self
+    }
+
+    /// The client for an account reference, if it is mapped.
+    pub fn client(&self, account: &str) -> Option<u16> {
+        self.accounts
+            .get(&normalize_account(account))
+            .copied()
+            .or(self.default_client)
+    }
+
+    /// Turn `entry` into a deposit or withdrawal, or `None` if its account is not mapped.
+    pub fn transaction(&self, entry: &StatementEntry) -> Option<Transaction> {
+        let client = self.client(&entry.account)?;
+        let r#type = if entry.amount.is_sign_negative() {
+            TransactionType::Withdrawal
+        } else {
+            TransactionType::Deposit
+        };
+        Some(Transaction {
+            r#type,
+            client,
+            tx: entry.tx_id(),
+            amount: Some(entry.amount.abs()),
+        })
+    }
+}
+
+impl FromStr for ClientMapping {
+    type Err = StatementError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mapping: ClientMapping = toml::from_str(s)?;
+        Ok(Self {
+            accounts: mapping
+                .accounts
+                .into_iter()
+                .map(|(account, client)| (normalize_account(&account), client))
+                .collect(),
+            ..mapping
+        })
+    }
+}
+
+fn normalize_account(account: &str) -> String {
+    account
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// StatementError represents a statement or mapping file that cannot be read.
+#[derive(Debug, Error)]
+pub enum StatementError {
+    #[error("Failed to read statement: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid XML: {0}")]
+    Xml(#[from] quick_xml::Error),
+    #[error("Invalid client mapping: {0}")]
+    Mapping(#[from] toml::de::Error),
+    #[error("Statement entry is missing {0}.")]
+    MissingField(&'static str),
+    #[error("Invalid amount '{0}'.")]
+    InvalidAmount(String),
+    #[error("References '{first}' and '{second}' both map to tx {tx}.")]
+    TxIdCollision {
+        tx: u32,
+        first: String,
+        second: String,
+    },
+}
+
+// OFX allows either a decimal point or a decimal comma. Without a configured separator
+// the last `.` or `,` is the decimal separator, unless it occurs more than once and so
+// groups thousands, as in `1,234,567`.
+fn detect_decimal_separator(amount: &str) -> char {
+    let Some(last) = amount.chars().rev().find(|c| matches!(c, '.' | ',')) else {
+        return '.';
+    };
+    match (last, amount.matches(last).count() > 1) {
+        ('.', true) => ',',
+        (',', true) => '.',
+        (last, _) => last,
+    }
+}
+
+/// Parse an OFX amount. The other of `.` and `,` than `decimal_separator` may group the
+/// integer digits in threes, e.g. `1,234.56` or `1.234,56`.
+fn parse_ofx_amount(
+    amount: &str,
+    decimal_separator: Option<char>,
+) -> Result<Decimal, StatementError> {
+    let invalid = || StatementError::InvalidAmount(amount.to_string());
+    let trimmed = amount.trim();
+    let decimal = decimal_separator.unwrap_or_else(|| detect_decimal_separator(trimmed));
+    let grouping = if decimal == ',' { '.' } else { ',' };
+    let (integer, fraction) = match trimmed.rsplit_once(decimal) {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (trimmed, None),
+    };
+
+    let mut groups = integer.split(grouping);
+    let mut normalized = groups.next().unwrap_or_default().to_string();
+    let leading = normalized.trim_start_matches(['-', '+']).len();
+    for group in groups {
+        if leading == 0 || leading > 3 || group.len() != 3 {
+            return Err(invalid());
+        }
+        normalized.push_str(group);
+    }
+    if let Some(fraction) = fraction {
+        normalized.push('.');
+        normalized.push_str(fraction);
+    }
+    Decimal::from_str(&normalized).map_err(|_| invalid())
+}
+
+fn parse_amount(amount: &str) -> Result<Decimal, StatementError> {
+    Decimal::from_str(amount.trim()).map_err(|_| StatementError::InvalidAmount(amount.to_string()))
+}
+
+// Different references sharing a tx id would have all but the first entry rejected by the
+// engine as duplicates, so the statement is rejected instead.
+fn check_tx_ids(entries: &[StatementEntry]) -> Result<(), StatementError> {
+    let mut references: HashMap<u32, &str> = HashMap::new();
+    for entry in entries {
+        let tx = entry.tx_id();
+        let first = *references.entry(tx).or_insert(&entry.reference);
+        if first != entry.reference {
+            return Err(StatementError::TxIdCollision {
+                tx,
+                first: first.to_string(),
+                second: entry.reference.clone(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Parse the transactions of an OFX statement. Both SGML (OFX 1.x, where leaf elements
+/// have no closing tags) and XML (OFX 2.x) files are accepted. Amounts use
+/// `decimal_separator`, or a separator detected per amount when it is `None`.
+pub fn parse_ofx<R: Read>(
+    mut reader: R,
+    decimal_separator: Option<char>,
+) -> Result<Vec<StatementEntry>, StatementError> {
+    // OFX 1.x files are often Latin-1 or Windows-1252, which only matters for free text.
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    let content = String::from_utf8_lossy(&bytes);
+
+    #[derive(Default)]
+    struct Pending {
+        reference: Option<String>,
+        amount: Option<Decimal>,
+    }
+
+    let mut entries = Vec::new();
+    let mut account = String::new();
+    // Transfers name the other account in BANKACCTTO or CCACCTTO, which also has an
+    // ACCTID, so only the statement account in BANKACCTFROM or CCACCTFROM is used.
+    let mut in_from_account = false;
+    let mut pending: Option<Pending> = None;
+
+    let mut rest = content.as_ref();
+    while let Some(start) = rest.find('<') {
+        let Some(end) = rest[start..].find('>') else {
+            break;
+        };
+        let tag = rest[start + 1..start + end].trim();
+        rest = &rest[start + end + 1..];
+        let value = rest[..rest.find('<').unwrap_or(rest.len())].trim();
+
+        match tag.to_ascii_uppercase().as_str() {
+            "BANKACCTFROM" | "CCACCTFROM" => in_from_account = true,
+            "/BANKACCTFROM" | "/CCACCTFROM" => in_from_account = false,
+            "ACCTID" if in_from_account => account = value.to_string(),
+            "STMTTRN" => pending = Some(Pending::default()),
+            "FITID" => {
+                if let Some(pending) = &mut pending {
+                    pending.reference = Some(value.to_string());
+                }
+            }
+            "TRNAMT" => {
+                if let Some(pending) = &mut pending {
+                    pending.amount = Some(parse_ofx_amount(value, decimal_separator)?);
+                }
+            }
+            "/STMTTRN" => {
+                let Some(pending) = pending.take() else {
+                    continue;
+                };
+                entries.push(StatementEntry {
+                    account: account.clone(),
+                    reference: pending
+                        .reference
+                        .ok_or(StatementError::MissingField("FITID"))?,
+                    amount: pending
+                        .amount
+                        .ok_or(StatementError::MissingField("TRNAMT"))?,
+                });
+            }
+            _ => {}
+        }
+    }
+    Ok(entries)
+}
+
+/// Parse the booked entries of a camt.053 statement. Pending entries are skipped and
+/// reversals (`RvslInd`) are applied in the opposite direction.
+pub fn parse_camt053<R: BufRead>(reader: R) -> Result<Vec<StatementEntry>, StatementError> {
+    #[derive(Default)]
+    struct Pending {
+        amount: Option<Decimal>,
+        debit: bool,
+        reversal: bool,
+        booked: bool,
+        account_servicer_ref: Option<String>,
+        entry_ref: Option<String>,
+    }
+
+    let mut xml = quick_xml::Reader::from_reader(reader);
+    let mut buf = Vec::new();
+    let mut path: Vec<String> = Vec::new();
+    let mut text = String::new();
+
+    let mut entries = Vec::new();
+    let mut account = String::new();
+    let mut pending: Option<Pending> = None;
+
+    loop {
+        match xml.read_event_into(&mut buf)? {
+            Event::Start(e) => {
+                let name = e.local_name().as_ref().to_string();
+                if name == "Ntry" {
+                    pending = Some(Pending {
+                        booked: true,
+                        ..Pending::default()
+                    });
+                }
+                path.push(name);
+                text.clear();
+            }
+            Event::Text(e) => text.push_str(&e.xml10_content()),
+            Event::CData(e) => text.push_str(&e.xml10_content()),
+            Event::GeneralRef(e) => match e.resolve_char_ref()? {
+                Some(c) => text.push(c),
+                None => {
+                    text.push_str(quick_xml::escape::resolve_predefined_entity(&e).unwrap_or(""))
+                }
+            },
+            Event::End(_) => {
+                let value = text.trim();
+                let tail: Vec<&str> = path.iter().rev().take(5).map(String::as_str).collect();
+                match (tail.as_slice(), &mut pending) {
+                    (["IBAN", "Id", "Acct", "Stmt", ..], _)
+                    | (["Id", "Othr", "Id", "Acct", "Stmt"], _) => account = value.to_string(),
+                    (["Amt", "Ntry", ..], Some(p)) => p.amount = Some(parse_amount(value)?),
+                    (["CdtDbtInd", "Ntry", ..], Some(p)) => p.debit = value == "DBIT",
+                    (["RvslInd", "Ntry", ..], Some(p)) => p.reversal = value == "true",
+                    // camt.053.001.02 has the code directly in Sts, later versions in Sts/Cd.
+                    (["Sts", "Ntry", ..] | ["Cd", "Sts", "Ntry", ..], Some(p))
+                        if !value.is_empty() =>
+                    {
+                        p.booked = value == "BOOK"
+                    }
+                    (["AcctSvcrRef", "Ntry", ..], Some(p)) => {
+                        p.account_servicer_ref = Some(value.to_string())
+                    }
+                    (["NtryRef", "Ntry", ..], Some(p)) => p.entry_ref = Some(value.to_string()),
+                    (["Ntry", ..], Some(_)) => {
+                        let p = pending.take().expect("pending entry");
+                        if p.booked {
+                            let amount = p.amount.ok_or(StatementError::MissingField("Amt"))?;
+                            let reference = p
+                                .account_servicer_ref
+                                .or(p.entry_ref)
+                                .ok_or(StatementError::MissingField("AcctSvcrRef or NtryRef"))?;
+                            entries.push(StatementEntry {
+                                account: account.clone(),
+                                reference,
+                                amount: if p.debit != p.reversal {
+                                    -amount
+                                } else {
+                                    amount
+                                },
+                            });
+                        }
+                    }
+                    _ => {}
+                }
+                path.pop();
+                text.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(entries)
+}
+
+/// StatementFormat selects the parser of a `StatementTransactionSource`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatementFormat {
+    Ofx,
+    Camt053,
+}
+
+/// StatementTransactionSource replays a bank statement as deposits and withdrawals.
+/// Entries of accounts missing from the `ClientMapping` are skipped with a warning.
+pub struct StatementTransactionSource {
+    input: Option<Input>,
+    format: StatementFormat,
+    mapping: ClientMapping,
+    malformed: MalformedRows,
+}
+
+impl StatementTransactionSource {
+    pub fn new(path: &str, format: StatementFormat, mapping: ClientMapping) -> Self {
+        Self {
+            input: Some(Input::Path(path.to_string())),
+            format,
+            mapping,
+            malformed: MalformedRows::default(),
+        }
+    }
+
+    pub fn from_reader<R: Read + 'static>(
+        reader: R,
+        format: StatementFormat,
+        mapping: ClientMapping,
+    ) -> Self {
+        Self {
+            input: Some(Input::Reader(Box::new(reader))),
+            format,
+            mapping,
+            malformed: MalformedRows::default(),
+        }
+    }
+}
+
+impl TransactionSource for StatementTransactionSource {
+    fn transactions(&mut self) -> Box<dyn Iterator<Item = Transaction>> {
+        let Some(input) = self.input.take() else {
+            return Box::new(std::iter::empty());
+        };
+        let reader = match input.open() {
+            Ok(reader) => reader,
+            Err(e) => {
+                self.malformed.skip("input", e);
+                return Box::new(std::iter::empty());
+            }
+        };
+        // Statements are parsed as a whole, an invalid one yields no transactions.
+        let entries = match self.format {
+            StatementFormat::Ofx => parse_ofx(reader, self.mapping.decimal_separator),
+            StatementFormat::Camt053 => parse_camt053(reader),
+        };
+        let entries = match entries.and_then(|entries| check_tx_ids(&entries).map(|()| entries)) {
+            Ok(entries) => entries,
+            Err(e) => {
+                self.malformed.skip("statement", e);
+                return Box::new(std::iter::empty());
+            }
+        };
+
+        let mapping = self.mapping.clone();
+        Box::new(entries.into_iter().filter_map(move |entry| {
+            let transaction = mapping.transaction(&entry);
+            if transaction.is_none() {
+                warn!(
+                    "Skipping entry {}: account {} is not mapped to a client.",
+                    entry.reference, entry.account
+                );
+            }
+            transaction
+        }))
+    }
+
+    fn malformed(&self) -> MalformedRows {
+        self.malformed.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OFX: &str = "OFXHEADER:100\n\
+        DATA:OFXSGML\n\
+        \n\
+        <OFX><BANKMSGSRSV1><STMTTRNRS><STMTRS>\n\
+        <BANKACCTFROM><BANKID>121099999<ACCTID>1234567890<ACCTTYPE>CHECKING</BANKACCTFROM>\n\
+        <BANKTRANLIST>\n\
+        <STMTTRN><TRNTYPE>CREDIT<DTPOSTED>20240601<TRNAMT>150.25<FITID>1001<NAME>Payroll</STMTTRN>\n\
+        <STMTTRN><TRNTYPE>DEBIT<DTPOSTED>20240602<TRNAMT>-20.00<FITID>ABC-7</STMTTRN>\n\
+        </BANKTRANLIST>\n\
+        </STMTRS></STMTTRNRS></BANKMSGSRSV1></OFX>\n";
+
+    const CAMT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+        <Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.053.001.08">
+          <BkToCstmrStmt>
+            <Stmt>
+              <Acct><Id><IBAN>DE89370400440532013000</IBAN></Id></Acct>
+              <Ntry>
+                <Amt Ccy="EUR">100.00</Amt><CdtDbtInd>CRDT</CdtDbtInd>
+                <Sts><Cd>BOOK</Cd></Sts><AcctSvcrRef>5001</AcctSvcrRef>
+              </Ntry>
+              <Ntry>
+                <Amt Ccy="EUR">30.50</Amt><CdtDbtInd>DBIT</CdtDbtInd>
+                <Sts><Cd>BOOK</Cd></Sts><NtryRef>5002</NtryRef>
+              </Ntry>
+              <Ntry>
+                <Amt Ccy="EUR">5.00</Amt><CdtDbtInd>CRDT</CdtDbtInd>
+                <Sts><Cd>PDNG</Cd></Sts><AcctSvcrRef>5003</AcctSvcrRef>
+              </Ntry>
+              <Ntry>
+                <Amt Ccy="EUR">10.00</Amt><CdtDbtInd>CRDT</CdtDbtInd><RvslInd>true</RvslInd>
+                <Sts>BOOK</Sts><AcctSvcrRef>5004</AcctSvcrRef>
+              </Ntry>
+            </Stmt>
+          </BkToCstmrStmt>
+        </Document>"#;
+
+    #[test]
+    fn test_parse_ofx() {
+        let entries = parse_ofx(OFX.as_bytes(), None).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                StatementEntry {
+                    account: "1234567890".to_string(),
+                    reference: "1001".to_string(),
+                    amount: Decimal::new(15025, 2),
+                },
+                StatementEntry {
+                    account: "1234567890".to_string(),
+                    reference: "ABC-7".to_string(),
+                    amount: Decimal::new(-2000, 2),
+                },
+            ]
+        );
+        assert_eq!(entries[0].tx_id(), 1001);
+        assert_eq!(entries[1].tx_id(), fnv1a("ABC-7"));
+    }
+
+    #[test]
+    fn test_parse_ofx_transfer_keeps_statement_account() {
+        let ofx = "<OFX><CREDITCARDMSGSRSV1><CCSTMTTRNRS><CCSTMTRS>\n\
+            <CCACCTFROM><ACCTID>4111222233334444</CCACCTFROM>\n\
+            <BANKTRANLIST>\n\
+            <STMTTRN><TRNTYPE>XFER<TRNAMT>-50.00<FITID>2001\n\
+            <BANKACCTTO><BANKID>121099999<ACCTID>9999999999<ACCTTYPE>SAVINGS</BANKACCTTO>\n\
+            </STMTTRN>\n\
+            <STMTTRN><TRNTYPE>CREDIT<TRNAMT>12.00<FITID>2002</STMTTRN>\n\
+            </BANKTRANLIST>\n\
+            </CCSTMTRS></CCSTMTTRNRS></CREDITCARDMSGSRSV1></OFX>\n";
+        let entries = parse_ofx(ofx.as_bytes(), None).unwrap();
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| (entry.account.as_str(), entry.reference.as_str()))
+                .collect::<Vec<_>>(),
+            vec![("4111222233334444", "2001"), ("4111222233334444", "2002")]
+        );
+    }
+
+    #[test]
+    fn test_parse_ofx_amount_separators() {
+        let amount = |value, separator| parse_ofx_amount(value, separator).ok();
+        assert_eq!(amount("-20,00", None), Some(Decimal::new(-2000, 2)));
+        assert_eq!(amount("1,234.56", None), Some(Decimal::new(123456, 2)));
+        assert_eq!(amount("-1.234,56", None), Some(Decimal::new(-123456, 2)));
+        assert_eq!(amount("1,234,567", None), Some(Decimal::new(1234567, 0)));
+        assert_eq!(amount("1,234", Some('.')), Some(Decimal::new(1234, 0)));
+        assert_eq!(amount("1,234", Some(',')), Some(Decimal::new(1234, 3)));
+        assert_eq!(
+            amount("1.234.567,5", Some(',')),
+            Some(Decimal::new(12345675, 1))
+        );
+        assert_eq!(amount("12,34.5", None), None);
+        assert_eq!(amount("1234,567.5", None), None);
+        assert_eq!(amount("1.5,00", Some('.')), None);
+    }
+
+    #[test]
+    fn test_colliding_tx_ids_reject_statement() {
+        let ofx = OFX.replace("<FITID>ABC-7", "<FITID>01001");
+        let mut source = StatementTransactionSource::from_reader(
+            std::io::Cursor::new(ofx),
+            StatementFormat::Ofx,
+            ClientMapping::new().with_default_client(1),
+        );
+        assert_eq!(source.transactions().count(), 0);
+        let malformed = source.malformed();
+        assert_eq!(malformed.count(), 1);
+        assert!(
+            malformed.errors()[0]
+                .to_string()
+                .contains("References '1001' and '01001' both map to tx 1001.")
+        );
+    }
+
+    #[test]
+    fn test_camt053_replayed_through_mapping() {
+        let mapping: ClientMapping = "[accounts]\n\"de89 3704 0044 0532 0130 00\" = 7"
+            .parse()
+            .unwrap();
+        let transactions: Vec<Transaction> = StatementTransactionSource::from_reader(
+            CAMT.as_bytes(),
+            StatementFormat::Camt053,
+            mapping,
+        )
+        .transactions()
+        .collect();
+
+        let summary: Vec<_> = transactions
+            .iter()
+            .map(|tx| (tx.r#type, tx.client, tx.tx, tx.amount.unwrap()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (TransactionType::Deposit, 7, 5001, Decimal::new(10000, 2)),
+                (TransactionType::Withdrawal, 7, 5002, Decimal::new(3050, 2)),
+                (TransactionType::Withdrawal, 7, 5004, Decimal::new(1000, 2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unmapped_accounts_skipped_or_defaulted() {
+        let source = |mapping| {
+            StatementTransactionSource::from_reader(OFX.as_bytes(), StatementFormat::Ofx, mapping)
+                .transactions()
+                .count()
+        };
+        assert_eq!(source(ClientMapping::new().with_account("999", 1)), 0);
+        assert_eq!(source(ClientMapping::new().with_default_client(3)), 2);
+        assert!(matches!(
+            "unknown = 1".parse::<ClientMapping>(),
+            Err(StatementError::Mapping(_))
+        ));
+    }
+}
@@ -0,0 +1,43 @@
+//! Fixtures shared by the unit tests.
+
+use std::fs;
+use std::path::PathBuf;
+
+use rust_decimal::Decimal;
+
+use crate::engine::Engine;
+use crate::transaction::{Transaction, TransactionType};
+
+/// A transaction with `amount` in hundredths, e.g. `Some(150)` for 1.50.
+pub(crate) fn tx(
+    r#type: TransactionType,
+    client: u16,
+    tx: u32,
+    amount: Option<i64>,
+) -> Transaction {
+    Transaction {
+        r#type,
+        client,
+        tx,
+        amount: amount.map(|a| Decimal::new(a, 2)),
+    }
+}
+
+/// Apply `transactions` to `engine` in order, ignoring rejections.
+pub(crate) fn apply_all(
+    mut engine: Engine,
+    transactions: impl IntoIterator<Item = Transaction>,
+) -> Engine {
+    for transaction in transactions {
+        let _ = engine.apply_transaction(transaction);
+    }
+    engine
+}
+
+/// An empty directory for the test `name`, unique to this process.
+pub(crate) fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("tx-engine-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -1,12 +1,18 @@
 use csv::{ReaderBuilder, StringRecord};
 use rust_decimal::Decimal;
-use serde::Deserialize;
-use std::fs::File;
+use serde::{Deserialize, Serialize};
 use std::io::BufRead;
-use std::io::BufReader;
+use std::io::Read;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use log::warn;
+
+use crate::dialect::{CsvDialect, ParseError, RowParser};
+use crate::input::Input;
 
 // TransactionType defines the type of transaction.
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum TransactionType {
     Deposit,
@@ -16,9 +22,31 @@ pub enum TransactionType {
     Chargeback,
 }
 
+impl TransactionType {
+    /// Every transaction type, in declaration order.
+    pub const ALL: [TransactionType; 5] = [
+        TransactionType::Deposit,
+        TransactionType::Withdrawal,
+        TransactionType::Dispute,
+        TransactionType::Resolve,
+        TransactionType::Chargeback,
+    ];
+
+    /// The name used in CSV rows, reports and logs, e.g. `deposit`.
+    pub fn name(self) -> &'static str {
+        match self {
+            TransactionType::Deposit => "deposit",
+            TransactionType::Withdrawal => "withdrawal",
+            TransactionType::Dispute => "dispute",
+            TransactionType::Resolve => "resolve",
+            TransactionType::Chargeback => "chargeback",
+        }
+    }
+}
+
 /// Transaction stores information about a financial transaction.
 /// amount is Optional. Only present for deposit/withdrawal
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Transaction {
     pub r#type: TransactionType, // `r#type` since "type" is reserved
     pub client: u16,
@@ -27,7 +55,8 @@ pub struct Transaction {
 }
 
 /// DisputeState represents the state of a transaction in a dispute.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum DisputeState {
     None,
     Disputed,
@@ -35,8 +64,20 @@ pub enum DisputeState {
     ChargedBack,
 }
 
+impl DisputeState {
+    /// The name used in statements and diffs, e.g. `charged_back`.
+    pub fn name(self) -> &'static str {
+        match self {
+            DisputeState::None => "none",
+            DisputeState::Disputed => "disputed",
+            DisputeState::Resolved => "resolved",
+            DisputeState::ChargedBack => "charged_back",
+        }
+    }
+}
+
 /// TransactionRecord combines a Transaction with its dispute state for storage.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransactionRecord {
     pub transaction: Transaction,
     pub dispute_state: DisputeState,
@@ -46,62 +87,364 @@ pub struct TransactionRecord {
 /// Think about transactions coming from CSV files, network, or other sources.
 pub trait TransactionSource {
     fn transactions(&mut self) -> Box<dyn Iterator<Item = Transaction>>;
+
+    /// The rows skipped so far because they could not be parsed.
+    fn malformed(&self) -> MalformedRows {
+        MalformedRows::default()
+    }
+
+    /// Number of rows skipped so far because they could not be parsed.
+    fn malformed_rows(&self) -> u64 {
+        self.malformed().count()
+    }
+}
+
+impl<T: TransactionSource + ?Sized> TransactionSource for Box<T> {
+    fn transactions(&mut self) -> Box<dyn Iterator<Item = Transaction>> {
+        (**self).transactions()
+    }
+
+    fn malformed(&self) -> MalformedRows {
+        (**self).malformed()
+    }
+}
+
+/// Number of malformed rows whose error message is kept by `MalformedRows`.
+pub const MAX_KEPT_ERRORS: usize = 100;
+
+/// MalformedRows counts the rows a source skipped and keeps the first
+/// `MAX_KEPT_ERRORS` error messages. An input that cannot be opened or has an invalid
+/// header is skipped as a whole and counted once. Clones share the count, so the
+/// iterator returned by `transactions` can update it.
+#[derive(Debug, Clone, Default)]
+pub struct MalformedRows {
+    count: Arc<AtomicU64>,
+    errors: Arc<Mutex<Vec<String>>>,
 }
 
-/// CsvTransactionSource reads transactions from a CSV file.
+impl MalformedRows {
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// The first error messages, each prefixed with where the row was found.
+    pub fn errors(&self) -> Vec<String> {
+        self.errors.lock().expect("Error list poisoned").clone()
+    }
+
+    /// Log the skipped row and count it.
+    pub(crate) fn skip(&self, location: impl std::fmt::Display, error: impl std::fmt::Display) {
+        warn!("Skipping malformed row at {location}: {error}");
+        self.count.fetch_add(1, Ordering::Relaxed);
+        let mut errors = self.errors.lock().expect("Error list poisoned");
+        if errors.len() < MAX_KEPT_ERRORS {
+            errors.push(format!("{location}: {error}"));
+        }
+    }
+}
+
+/// CsvTransactionSource reads transactions from a CSV file, standard input (`-`) or any
+/// reader. Gzip and zstd compressed input is detected and decompressed while streaming.
+/// Column names, separators and type names follow its `CsvDialect`.
 pub struct CsvTransactionSource {
-    pub path: String,
+    input: Option<Input>,
+    dialect: CsvDialect,
+    malformed: MalformedRows,
 }
 
 impl CsvTransactionSource {
     pub fn new(path: &str) -> Self {
         Self {
-            path: path.to_string(),
+            input: Some(Input::Path(path.to_string())),
+            dialect: CsvDialect::default(),
+            malformed: MalformedRows::default(),
+        }
+    }
+
+    /// Read CSV from `reader`, e.g. a socket or an in-memory buffer.
+    pub fn from_reader<R: Read + 'static>(reader: R) -> Self {
+        Self {
+            input: Some(Input::Reader(Box::new(reader))),
+            dialect: CsvDialect::default(),
+            malformed: MalformedRows::default(),
         }
     }
+
+    /// Read the input using `dialect` instead of the default layout.
+    pub fn with_dialect(mut self, dialect: CsvDialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
 }
 
 /// Utility function to parse transactions from a CSV file, trimming whitespace from headers.
 pub fn parse_transactions_with_trimmed_headers(path: &str) -> Vec<Transaction> {
-    let file = File::open(path).expect("Failed to open CSV file");
-    let mut reader = BufReader::new(file);
-
-    // Read the first line (headers)
-    let mut first_line = String::new();
-    reader
-        .read_line(&mut first_line)
-        .expect("Failed to read header line");
-    let headers: Vec<String> = first_line
+    CsvTransactionSource::new(path).transactions().collect()
+}
+
+/// Stream transactions from CSV in `reader`. Headers and fields are trimmed. Rows that
+/// cannot be read or parsed are skipped and counted in `malformed`.
+fn read_csv_transactions(
+    reader: Box<dyn BufRead>,
+    dialect: &CsvDialect,
+    malformed: MalformedRows,
+) -> Box<dyn Iterator<Item = Transaction>> {
+    let mut rdr = dialect.reader_builder().from_reader(reader);
+    let headers = match rdr.headers() {
+        Ok(headers) => headers.clone(),
+        Err(e) => {
+            malformed.skip("header", e);
+            return Box::new(std::iter::empty());
+        }
+    };
+    if headers.is_empty() {
+        return Box::new(std::iter::empty());
+    }
+    // Without the header no row can be parsed, so the whole input is skipped.
+    let parser = match dialect.row_parser(&headers) {
+        Ok(parser) => parser,
+        Err(e) => {
+            malformed.skip("header", e);
+            return Box::new(std::iter::empty());
+        }
+    };
+
+    let mut records = rdr.into_records();
+    Box::new(std::iter::from_fn(move || {
+        loop {
+            let record = match records.next()? {
+                Ok(record) => record,
+                Err(e) => {
+                    let line = e.position().map_or(0, csv::Position::line);
+                    let io_error = matches!(e.kind(), csv::ErrorKind::Io(_));
+                    malformed.skip(format_args!("line {line}"), e);
+                    // The reader cannot recover from I/O errors, stop instead of retrying.
+                    if io_error {
+                        return None;
+                    }
+                    continue;
+                }
+            };
+            match parser.parse(&record) {
+                Ok(transaction) => return Some(transaction),
+                Err(e) => {
+                    let line = record.position().map_or(0, csv::Position::line);
+                    malformed.skip(format_args!("line {line}"), e);
+                }
+            }
+        }
+    }))
+}
+
+/// Split a CSV header line into a record of trimmed column names.
+pub fn parse_trimmed_headers(line: &str) -> StringRecord {
+    line.trim_end_matches(['\r', '\n'])
         .split(',')
-        .map(|h| h.trim().to_string())
-        .collect();
-
-    // Build a CSV reader with custom headers and trimming
-    let mut rdr = ReaderBuilder::new()
-        .has_headers(false)
-        .trim(csv::Trim::All)
-        .from_reader(reader);
-
-    // Set the cleaned headers
-    let header_record = StringRecord::from(headers);
-    let records = rdr.records();
-
-    // Iterator that deserializes each record
-    let txs: Vec<Transaction> = records
-        .map(|result| {
-            let record = result.expect("Failed to read record");
-            record
-                .deserialize(Some(&header_record))
-                .expect("Failed to parse transaction")
+        .map(str::trim)
+        .collect()
+}
+
+// Initial read buffer of a `LineParser`, enough for typical rows. Longer rows still parse.
+const LINE_BUFFER_CAPACITY: usize = 256;
+
+/// LineParser parses CSV data rows that arrive one line at a time, such as network
+/// streams, against the header line it was built from.
+pub struct LineParser {
+    reader_builder: ReaderBuilder,
+    parser: RowParser,
+    record: StringRecord,
+}
+
+impl LineParser {
+    /// Resolve the columns of `header`, failing if a required column is missing.
+    pub fn new(header: &str) -> Result<Self, ParseError> {
+        let dialect = CsvDialect::default();
+        let parser = dialect.row_parser(&parse_trimmed_headers(header))?;
+        let mut reader_builder = dialect.reader_builder();
+        reader_builder
+            .has_headers(false)
+            .buffer_capacity(LINE_BUFFER_CAPACITY);
+        Ok(Self {
+            reader_builder,
+            parser,
+            record: StringRecord::new(),
         })
-        .collect();
+    }
 
-    txs
+    pub fn parse(&mut self, row: &str) -> Result<Transaction, ParseError> {
+        let mut reader = self.reader_builder.from_reader(row.as_bytes());
+        reader.read_record(&mut self.record)?;
+        self.parser.parse(&self.record)
+    }
 }
 
 impl TransactionSource for CsvTransactionSource {
     fn transactions(&mut self) -> Box<dyn Iterator<Item = Transaction>> {
-        let txs = parse_transactions_with_trimmed_headers(&self.path);
-        Box::new(txs.into_iter())
+        let Some(input) = self.input.take() else {
+            return Box::new(std::iter::empty());
+        };
+        match input.open() {
+            Ok(reader) => read_csv_transactions(reader, &self.dialect, self.malformed.clone()),
+            Err(e) => {
+                self.malformed.skip("input", e);
+                Box::new(std::iter::empty())
+            }
+        }
+    }
+
+    fn malformed(&self) -> MalformedRows {
+        self.malformed.clone()
+    }
+}
+
+/// JsonLinesTransactionSource reads transactions from JSON Lines, one object per line
+/// with the same field names as `Transaction`. Amounts may be strings or numbers.
+/// Like `CsvTransactionSource` it accepts a path, `-` or any reader, compressed or not.
+pub struct JsonLinesTransactionSource {
+    input: Option<Input>,
+    malformed: MalformedRows,
+}
+
+impl JsonLinesTransactionSource {
+    pub fn new(path: &str) -> Self {
+        Self {
+            input: Some(Input::Path(path.to_string())),
+            malformed: MalformedRows::default(),
+        }
+    }
+
+    pub fn from_reader<R: Read + 'static>(reader: R) -> Self {
+        Self {
+            input: Some(Input::Reader(Box::new(reader))),
+            malformed: MalformedRows::default(),
+        }
+    }
+}
+
+impl TransactionSource for JsonLinesTransactionSource {
+    fn transactions(&mut self) -> Box<dyn Iterator<Item = Transaction>> {
+        let Some(input) = self.input.take() else {
+            return Box::new(std::iter::empty());
+        };
+        let malformed = self.malformed.clone();
+        let mut lines = match input.open() {
+            Ok(reader) => reader.lines().enumerate(),
+            Err(e) => {
+                malformed.skip("input", e);
+                return Box::new(std::iter::empty());
+            }
+        };
+        Box::new(std::iter::from_fn(move || {
+            loop {
+                let (index, line) = lines.next()?;
+                let line = match line {
+                    Ok(line) => line,
+                    Err(e) => {
+                        malformed.skip(format_args!("line {}", index + 1), e);
+                        return None;
+                    }
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str(&line) {
+                    Ok(transaction) => return Some(transaction),
+                    Err(e) => malformed.skip(format_args!("line {}", index + 1), e),
+                }
+            }
+        }))
+    }
+
+    fn malformed(&self) -> MalformedRows {
+        self.malformed.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_json_lines_transaction() {
+        let tx: Transaction = serde_json::from_str(
+            r#"{"type": "deposit", "client": 1, "tx": 2, "amount": "1.2345"}"#,
+        )
+        .unwrap();
+        assert_eq!(tx.r#type, TransactionType::Deposit);
+        assert_eq!(tx.amount, Some(Decimal::new(12345, 4)));
+
+        let tx: Transaction =
+            serde_json::from_str(r#"{"type": "withdrawal", "client": 1, "tx": 3, "amount": 2.5}"#)
+                .unwrap();
+        assert_eq!(tx.amount, Some(Decimal::new(25, 1)));
+
+        let tx: Transaction =
+            serde_json::from_str(r#"{"type": "dispute", "client": 1, "tx": 2}"#).unwrap();
+        assert_eq!(tx.amount, None);
+    }
+
+    #[test]
+    fn test_csv_source_from_reader() {
+        let csv = "type,client,tx,amount\ndeposit,1,1,1.0\nwithdrawal, 1, 2, 0.5\n";
+        let transactions: Vec<Transaction> = CsvTransactionSource::from_reader(csv.as_bytes())
+            .transactions()
+            .collect();
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(transactions[1].r#type, TransactionType::Withdrawal);
+        assert_eq!(transactions[1].amount, Some(Decimal::new(5, 1)));
+    }
+
+    #[test]
+    fn test_malformed_rows_are_skipped_and_counted() {
+        let csv = "type,client,tx,amount
+                   deposit,1,1,1.0
+                   refund,1,2,1.0
+                   deposit,x,3,1.0
+                   deposit,1,4,2.0
+";
+        let mut source = CsvTransactionSource::from_reader(csv.as_bytes());
+        let transactions: Vec<Transaction> = source.transactions().collect();
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(transactions[1].tx, 4);
+        assert_eq!(source.malformed_rows(), 2);
+        assert_eq!(source.malformed().errors().len(), 2);
+        assert!(source.malformed().errors()[0].starts_with("line 3: "));
+
+        let jsonl =
+            "{\"type\": \"deposit\", \"client\": 1, \"tx\": 1, \"amount\": 1}\n{\"type\": 5}\n";
+        let mut source = JsonLinesTransactionSource::from_reader(jsonl.as_bytes());
+        assert_eq!(source.transactions().count(), 1);
+        assert_eq!(source.malformed_rows(), 1);
+    }
+
+    #[test]
+    fn test_line_parser() {
+        let mut parser = LineParser::new("type, client, tx, amount").unwrap();
+        let transaction = parser.parse("deposit,1,2,\"1.5\"").unwrap();
+        assert_eq!(transaction.tx, 2);
+        assert_eq!(transaction.amount, Some(Decimal::new(15, 1)));
+        assert!(matches!(
+            parser.parse("deposit, 1"),
+            Err(ParseError::InvalidField { .. })
+        ));
+
+        assert!(matches!(
+            LineParser::new("type, client, amount"),
+            Err(ParseError::MissingColumn(column)) if column == "tx"
+        ));
+    }
+
+    #[test]
+    fn test_invalid_header_skips_input() {
+        for input in [&b"type,client\ndeposit,1\n"[..], b"type,cli\xffent,tx\n"] {
+            let mut source = CsvTransactionSource::from_reader(input);
+            assert_eq!(source.transactions().count(), 0);
+            assert_eq!(source.malformed_rows(), 1);
+            assert!(source.malformed().errors()[0].starts_with("header: "));
+        }
+
+        let mut source = CsvTransactionSource::new("tests/does-not-exist.csv");
+        assert_eq!(source.transactions().count(), 0);
+        assert_eq!(source.malformed_rows(), 1);
     }
 }
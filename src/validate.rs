@@ -0,0 +1,224 @@
+//! Dry run of an input against a scratch engine, to check a file before loading it.
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use rust_decimal::Decimal;
+
+use crate::engine::{Engine, EngineError};
+use crate::transaction::{MAX_KEPT_ERRORS, Transaction, TransactionSource, TransactionType};
+
+/// Rejection is a row the scratch engine rejected.
+#[derive(Debug)]
+pub struct Rejection {
+    pub transaction: Transaction,
+    pub error: EngineError,
+}
+
+/// Movement is what the input would do to one client's funds.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Movement {
+    pub deposits: Decimal,
+    pub withdrawals: Decimal,
+    pub chargebacks: Decimal,
+}
+
+impl Movement {
+    /// Net change of the client's total funds.
+    pub fn net(&self) -> Decimal {
+        self.deposits - self.withdrawals - self.chargebacks
+    }
+}
+
+/// ValidationReport describes what applying an input would do.
+#[derive(Debug, Default)]
+pub struct ValidationReport {
+    /// Rows that were parsed.
+    pub rows: u64,
+    /// Rows that could not be parsed.
+    pub malformed: u64,
+    /// The first `MAX_KEPT_ERRORS` parse errors.
+    pub parse_errors: Vec<String>,
+    /// Rejections by `EngineError::code`.
+    pub rejected: BTreeMap<&'static str, u64>,
+    /// The first `MAX_KEPT_ERRORS` rejected rows.
+    pub rejections: Vec<Rejection>,
+    /// Clients whose accounts would be locked.
+    pub locked: Vec<u16>,
+    /// Movement of funds per client.
+    pub movements: BTreeMap<u16, Movement>,
+}
+
+/// Parse every row of `source` and apply it to a scratch engine. Nothing outside the
+/// scratch engine is changed.
+pub fn validate<T: TransactionSource + ?Sized>(source: &mut T) -> ValidationReport {
+    let mut engine = Engine::new();
+    let mut report = ValidationReport::default();
+
+    for transaction in source.transactions() {
+        report.rows += 1;
+        let Transaction {
+            r#type, client, tx, ..
+        } = transaction;
+        // Chargebacks carry no amount, the funds are those of the disputed transaction.
+        let disputed_amount = engine
+            .transactions
+            .get(&tx)
+            .and_then(|record| record.transaction.amount);
+
+        match engine.apply_transaction(transaction.clone()) {
+            Ok(()) => {
+                let movement = report.movements.entry(client).or_default();
+                let amount = transaction.amount.unwrap_or_default();
+                match r#type {
+                    TransactionType::Deposit => movement.deposits += amount,
+                    TransactionType::Withdrawal => movement.withdrawals += amount,
+                    TransactionType::Chargeback => {
+                        movement.chargebacks += disputed_amount.unwrap_or_default()
+                    }
+                    TransactionType::Dispute | TransactionType::Resolve => {}
+                }
+            }
+            Err(error) => {
+                *report.rejected.entry(error.code()).or_default() += 1;
+                if report.rejections.len() < MAX_KEPT_ERRORS {
+                    report.rejections.push(Rejection { transaction, error });
+                }
+            }
+        }
+    }
+
+    let malformed = source.malformed();
+    report.malformed = malformed.count();
+    report.parse_errors = malformed.errors();
+    report.locked = engine
+        .account_summaries()
+        .into_iter()
+        .filter(|summary| summary.locked)
+        .map(|summary| summary.client)
+        .collect();
+    report
+}
+
+impl ValidationReport {
+    pub fn rejected_total(&self) -> u64 {
+        self.rejected.values().sum()
+    }
+
+    /// Write the report as text. Lists of errors are cut off after `MAX_KEPT_ERRORS`.
+    pub fn write<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(
+            writer,
+            "{} rows parsed, {} malformed, {} rejected",
+            self.rows,
+            self.malformed,
+            self.rejected_total()
+        )?;
+
+        if !self.parse_errors.is_empty() {
+            writeln!(writer, "\nMalformed rows:")?;
+            for error in &self.parse_errors {
+                writeln!(writer, "  {error}")?;
+            }
+            more(writer, self.malformed, self.parse_errors.len())?;
+        }
+
+        if !self.rejections.is_empty() {
+            writeln!(writer, "\nRejected rows:")?;
+            for (code, count) in &self.rejected {
+                writeln!(writer, "  {code}: {count}")?;
+            }
+            for Rejection { transaction, error } in &self.rejections {
+                writeln!(
+                    writer,
+                    "  tx {} (client {}, {}): {}",
+                    transaction.tx,
+                    transaction.client,
+                    transaction.r#type.name(),
+                    error
+                )?;
+            }
+            more(writer, self.rejected_total(), self.rejections.len())?;
+        }
+
+        if !self.locked.is_empty() {
+            let locked: Vec<String> = self.locked.iter().map(u16::to_string).collect();
+            writeln!(
+                writer,
+                "\nAccounts that would be locked: {}",
+                locked.join(", ")
+            )?;
+        }
+
+        writeln!(writer, "\nclient, deposits, withdrawals, chargebacks, net")?;
+        for (client, movement) in &self.movements {
+            writeln!(
+                writer,
+                "{}, {}, {}, {}, {}",
+                client,
+                movement.deposits,
+                movement.withdrawals,
+                movement.chargebacks,
+                movement.net()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn more<W: Write + ?Sized>(writer: &mut W, total: u64, shown: usize) -> io::Result<()> {
+    let hidden = total.saturating_sub(shown as u64);
+    if hidden > 0 {
+        writeln!(writer, "  ... and {hidden} more")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::CsvTransactionSource;
+
+    #[test]
+    fn test_validate_reports_without_side_effects() {
+        let csv = "type, client, tx, amount\n\
+                   deposit, 1, 1, 10.0\n\
+                   deposit, 2, 2, 5.0\n\
+                   withdrawal, 1, 3, 2.5\n\
+                   refund, 1, 4, 1.0\n\
+                   withdrawal, 2, 5, 9.0\n\
+                   dispute, 2, 2,\n\
+                   chargeback, 2, 2,\n\
+                   deposit, 2, 6, 1.0\n";
+        let mut source = CsvTransactionSource::from_reader(csv.as_bytes());
+        let report = validate(&mut source);
+
+        assert_eq!(report.rows, 7);
+        assert_eq!(report.malformed, 1);
+        assert_eq!(report.locked, vec![2]);
+        assert_eq!(report.movements[&1].net(), Decimal::new(75, 1));
+        assert_eq!(report.movements[&2].net(), Decimal::ZERO);
+
+        let mut output = Vec::new();
+        report.write(&mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "7 rows parsed, 1 malformed, 2 rejected\n\
+             \n\
+             Malformed rows:\n  \
+             line 5: Unknown transaction type 'refund'.\n\
+             \n\
+             Rejected rows:\n  \
+             account_locked: 1\n  \
+             insufficient_funds: 1\n  \
+             tx 5 (client 2, withdrawal): Insufficient funds for client 2.\n  \
+             tx 6 (client 2, deposit): Account 2 is locked.\n\
+             \n\
+             Accounts that would be locked: 2\n\
+             \n\
+             client, deposits, withdrawals, chargebacks, net\n\
+             1, 10.0, 2.5, 0, 7.5\n\
+             2, 5.0, 0, 5.0, 0.0\n"
+        );
+    }
+}
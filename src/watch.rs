@@ -0,0 +1,516 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::engine::Engine;
+use crate::multi_file::{self, FileOrder, MultiFileTransactionSource};
+use crate::snapshot::Snapshot;
+use crate::transaction::LineParser;
+
+/// Default time between checks for new files or new lines.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Name of the manifest file kept in the archive folder unless configured otherwise.
+pub const DEFAULT_MANIFEST_NAME: &str = ".manifest.json";
+
+/// InboxWatcher applies CSV files dropped into an inbox directory.
+///
+/// Each file is applied once and then moved to the archive folder, under a new name if
+/// the archive already holds a file with its name. Files are recognised by their
+/// content, so a file already applied, e.g. because the process stopped before it was
+/// moved, is archived without being applied again, while a new file reusing an old
+/// name is applied. After each poll that applied files the engine is checkpointed: a
+/// snapshot is written to its own file next to the manifest, and the manifest, which
+/// lists the applied files and names the snapshot, is replaced atomically. Only then
+/// are the files of the poll moved, so after a crash they are applied again to the
+/// state saved before them. `restore_engine` loads the snapshot after a restart.
+/// Producers should write files under another name (or elsewhere) and rename them into
+/// the inbox once complete; only CSV files, possibly compressed, are picked up.
+pub struct InboxWatcher {
+    inbox: PathBuf,
+    archive: PathBuf,
+    manifest_path: PathBuf,
+    poll_interval: Duration,
+    processed: HashSet<String>,
+    manifest: Manifest,
+}
+
+/// Manifest lists the applied files and names the snapshot of the engine after them.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    processed: Vec<ProcessedFile>,
+    /// File name of the snapshot, in the directory of the manifest.
+    snapshot: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ProcessedFile {
+    /// Size and content hash, see `content_key`.
+    key: String,
+    /// Name the file had in the inbox.
+    name: String,
+}
+
+impl InboxWatcher {
+    /// Create a watcher, creating the archive folder and loading the manifest.
+    pub fn new(inbox: impl Into<PathBuf>, archive: impl Into<PathBuf>) -> io::Result<Self> {
+        let archive = archive.into();
+        let manifest = archive.join(DEFAULT_MANIFEST_NAME);
+        Self::with_manifest(inbox, archive, manifest)
+    }
+
+    /// Create a watcher that keeps its manifest at `manifest`.
+    pub fn with_manifest(
+        inbox: impl Into<PathBuf>,
+        archive: impl Into<PathBuf>,
+        manifest: impl Into<PathBuf>,
+    ) -> io::Result<Self> {
+        let archive = archive.into();
+        let manifest_path = manifest.into();
+        fs::create_dir_all(&archive)?;
+
+        let manifest: Manifest = match File::open(&manifest_path) {
+            Ok(file) => serde_json::from_reader(BufReader::new(file))
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Manifest::default(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(Self {
+            inbox: inbox.into(),
+            archive,
+            manifest_path,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            processed: manifest
+                .processed
+                .iter()
+                .map(|file| file.key.clone())
+                .collect(),
+            manifest,
+        })
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// The engine as saved with the last checkpoint, or an empty engine on the first
+    /// run. Pass it to `poll_once` or `run` to carry on where the last run stopped.
+    pub fn restore_engine(&self) -> io::Result<Engine> {
+        let Some(snapshot) = &self.manifest.snapshot else {
+            return Ok(Engine::new());
+        };
+        let file = File::open(self.manifest_dir().join(snapshot))?;
+        let snapshot = Snapshot::read_json(BufReader::new(file))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(snapshot.restore())
+    }
+
+    /// Apply every new file currently in the inbox, in name order, checkpoint the engine
+    /// and return the archived paths of all files that were picked up.
+    pub fn poll_once(&mut self, engine: &mut Engine) -> io::Result<Vec<PathBuf>> {
+        let mut files: Vec<PathBuf> = fs::read_dir(&self.inbox)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && multi_file::is_csv_path(path))
+            .collect();
+        files.sort();
+
+        let mut applied = 0;
+        for file in &files {
+            let key = content_key(file)?;
+            if self.processed.contains(&key) {
+                warn!(
+                    "{} was already processed, archiving it again.",
+                    file.display()
+                );
+            } else {
+                info!("Applying {}", file.display());
+                MultiFileTransactionSource::new(vec![file.clone()], FileOrder::Name)
+                    .process(engine);
+                self.processed.insert(key.clone());
+                self.manifest.processed.push(ProcessedFile {
+                    key,
+                    name: file_name(file),
+                });
+                applied += 1;
+            }
+        }
+        if applied > 0 {
+            self.checkpoint(engine)?;
+        }
+
+        let mut archived = Vec::new();
+        for file in files {
+            let path = unique_path(&self.archive, &file_name(&file));
+            move_file(&file, &path)?;
+            archived.push(path);
+        }
+        Ok(archived)
+    }
+
+    /// Watch the inbox forever, calling `on_batch` after each poll that applied files.
+    pub fn run<F: FnMut(&Engine)>(
+        &mut self,
+        engine: &mut Engine,
+        mut on_batch: F,
+    ) -> io::Result<()> {
+        loop {
+            if !self.poll_once(engine)?.is_empty() {
+                on_batch(engine);
+            }
+            thread::sleep(self.poll_interval);
+        }
+    }
+
+    fn manifest_dir(&self) -> &Path {
+        self.manifest_path.parent().unwrap_or(Path::new("."))
+    }
+
+    // Write the engine state to a new snapshot file, then replace the manifest
+    // atomically with one naming it and the files applied so far, and remove the old
+    // snapshot. After a crash the manifest either lists the files of this poll with the
+    // snapshot of the balances they produced, or neither.
+    fn checkpoint(&mut self, engine: &Engine) -> io::Result<()> {
+        let snapshot = format!(".snapshot-{}.json", self.manifest.processed.len());
+        write_synced(&self.manifest_dir().join(&snapshot), |writer| {
+            Snapshot::from_engine(engine).write_json(writer)
+        })?;
+
+        let previous = self.manifest.snapshot.replace(snapshot);
+        let partial = self.manifest_path.with_extension("tmp");
+        write_synced(&partial, |writer| {
+            serde_json::to_writer(writer, &self.manifest)
+        })?;
+        fs::rename(&partial, &self.manifest_path)?;
+
+        if let Some(previous) = previous {
+            let previous = self.manifest_dir().join(previous);
+            if let Err(e) = fs::remove_file(&previous) {
+                warn!("Failed to remove {}: {}", previous.display(), e);
+            }
+        }
+        Ok(())
+    }
+}
+
+// Create `path`, write it with `write` and sync it to disk.
+fn write_synced<F>(path: &Path, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut BufWriter<File>) -> serde_json::Result<()>,
+{
+    let mut writer = BufWriter::new(File::create(path)?);
+    write(&mut writer)?;
+    writer.flush()?;
+    writer.get_ref().sync_all()
+}
+
+// Identify a file by its size and 64-bit FNV-1a hash of its content.
+fn content_key(path: &Path) -> io::Result<String> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let mut size: u64 = 0;
+    let mut buffer = [0; 8192];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        for byte in &buffer[..read] {
+            hash = (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3);
+        }
+        size += read as u64;
+    }
+    Ok(format!("{size}-{hash:016x}"))
+}
+
+/// FileTailer follows a growing CSV file like `tail -f`, applying rows as they are appended.
+/// If the file shrinks it is assumed to have been truncated and is read again from the start.
+/// Lines that are not valid UTF-8 are skipped.
+pub struct FileTailer {
+    path: PathBuf,
+    // Offset just past the last complete line that was read.
+    position: u64,
+    parser: Option<LineParser>,
+    poll_interval: Duration,
+}
+
+impl FileTailer {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            position: 0,
+            parser: None,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+        }
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Apply all complete rows appended since the last call and return how many rows
+    /// were read. An incomplete last line is kept until its newline arrives.
+    pub fn poll_once(&mut self, engine: &mut Engine) -> io::Result<usize> {
+        let mut file = File::open(&self.path)?;
+        let len = file.metadata()?.len();
+        if len < self.position {
+            warn!(
+                "{} was truncated, reading from the start.",
+                self.path.display()
+            );
+            self.position = 0;
+            self.parser = None;
+        }
+        if len == self.position {
+            return Ok(0);
+        }
+
+        file.seek(SeekFrom::Start(self.position))?;
+        let mut appended = Vec::new();
+        file.take(len - self.position).read_to_end(&mut appended)?;
+        // An incomplete last line, possibly ending in the middle of a character, is
+        // read again once its newline arrives.
+        let Some(last_newline) = appended.iter().rposition(|byte| *byte == b'\n') else {
+            return Ok(0);
+        };
+        self.position += last_newline as u64 + 1;
+
+        let mut rows = 0;
+        for line in appended[..last_newline].split(|byte| *byte == b'\n') {
+            let Ok(line) = std::str::from_utf8(line) else {
+                warn!(
+                    "Skipping line of {} that is not valid UTF-8.",
+                    self.path.display()
+                );
+                continue;
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let Some(parser) = &mut self.parser else {
+                let parser = LineParser::new(line)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                self.parser = Some(parser);
+                continue;
+            };
+            rows += 1;
+            match parser.parse(line) {
+                // Rejections are logged by the engine.
+                Ok(transaction) => {
+                    let _ = engine.apply_transaction(transaction);
+                }
+                Err(e) => warn!("Failed to parse transaction '{}': {}", line, e),
+            }
+        }
+        Ok(rows)
+    }
+
+    /// Follow the file forever, calling `on_batch` after each poll that read rows.
+    pub fn run<F: FnMut(&Engine)>(
+        &mut self,
+        engine: &mut Engine,
+        mut on_batch: F,
+    ) -> io::Result<()> {
+        loop {
+            if self.poll_once(engine)? > 0 {
+                on_batch(engine);
+            }
+            thread::sleep(self.poll_interval);
+        }
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+// `name` in `dir`, or if that is taken the first free `<stem>.<n>.<extension>`.
+fn unique_path(dir: &Path, name: &str) -> PathBuf {
+    let path = dir.join(name);
+    if !path.exists() {
+        return path;
+    }
+    let name = Path::new(name);
+    let stem = name.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = name.extension().unwrap_or_default().to_string_lossy();
+    (1..)
+        .map(|n| dir.join(format!("{stem}.{n}.{extension}")))
+        .find(|path| !path.exists())
+        .expect("Unbounded range")
+}
+
+// Rename if possible, otherwise copy and remove (e.g. across file systems). `to` must
+// not exist, see `unique_path`.
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).is_err() {
+        fs::copy(from, to)?;
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+    use rust_decimal::Decimal;
+    use std::fs::OpenOptions;
+
+    #[test]
+    fn test_inbox_files_applied_once_and_archived() {
+        let dir = temp_dir("watch-inbox");
+        let inbox = dir.join("inbox");
+        let archive = dir.join("archive");
+        fs::create_dir_all(&inbox).unwrap();
+
+        let first = "type,client,tx,amount\ndeposit,1,1,5.0\n";
+        fs::write(inbox.join("00.csv"), first).unwrap();
+        fs::write(inbox.join("notes.txt"), "ignored").unwrap();
+
+        let mut watcher = InboxWatcher::new(&inbox, &archive).unwrap();
+        let mut engine = watcher.restore_engine().unwrap();
+        assert_eq!(
+            watcher.poll_once(&mut engine).unwrap(),
+            vec![archive.join("00.csv")]
+        );
+        assert!(!inbox.join("00.csv").exists());
+        assert!(inbox.join("notes.txt").exists());
+        assert!(watcher.poll_once(&mut engine).unwrap().is_empty());
+
+        // After a restart the balances come back from the manifest. The same file
+        // dropped again is archived but not re-applied, while a new file reusing its
+        // name is applied, and neither overwrites the archived file.
+        fs::write(inbox.join("00.csv"), first).unwrap();
+        let mut watcher = InboxWatcher::new(&inbox, &archive).unwrap();
+        let mut engine = watcher.restore_engine().unwrap();
+        assert_eq!(engine.accounts[&1].total(), Decimal::new(50, 1));
+        assert_eq!(
+            watcher.poll_once(&mut engine).unwrap(),
+            vec![archive.join("00.1.csv")]
+        );
+        assert_eq!(engine.accounts[&1].total(), Decimal::new(50, 1));
+
+        fs::write(
+            inbox.join("00.csv"),
+            "type,client,tx,amount\ndeposit,1,2,1.0\n",
+        )
+        .unwrap();
+        let mut watcher = InboxWatcher::new(&inbox, &archive).unwrap();
+        let mut engine = watcher.restore_engine().unwrap();
+        assert_eq!(
+            watcher.poll_once(&mut engine).unwrap(),
+            vec![archive.join("00.2.csv")]
+        );
+        assert_eq!(engine.accounts[&1].total(), Decimal::new(60, 1));
+        assert_eq!(fs::read_to_string(archive.join("00.csv")).unwrap(), first);
+
+        let engine = InboxWatcher::new(&inbox, &archive)
+            .unwrap()
+            .restore_engine()
+            .unwrap();
+        assert_eq!(engine.accounts[&1].total(), Decimal::new(60, 1));
+        assert!(engine.transactions.contains_key(&2));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_compressed_files_and_checkpoint_per_poll() {
+        use flate2::{Compression, write::GzEncoder};
+
+        let dir = temp_dir("watch-checkpoint");
+        let inbox = dir.join("inbox");
+        let archive = dir.join("archive");
+        fs::create_dir_all(&inbox).unwrap();
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(b"type,client,tx,amount\ndeposit,1,2,2.0\n")
+            .unwrap();
+        fs::write(inbox.join("01.csv.gz"), encoder.finish().unwrap()).unwrap();
+        fs::write(
+            inbox.join("00.csv"),
+            "type,client,tx,amount\ndeposit,1,1,5.0\n",
+        )
+        .unwrap();
+
+        let mut watcher = InboxWatcher::new(&inbox, &archive).unwrap();
+        let mut engine = watcher.restore_engine().unwrap();
+        assert_eq!(
+            watcher.poll_once(&mut engine).unwrap(),
+            vec![archive.join("00.csv"), archive.join("01.csv.gz")]
+        );
+        assert_eq!(engine.accounts[&1].total(), Decimal::new(70, 1));
+
+        // One snapshot for the poll, kept out of the manifest.
+        let snapshots = |archive: &Path| {
+            fs::read_dir(archive)
+                .unwrap()
+                .map(|entry| file_name(&entry.unwrap().path()))
+                .filter(|name| name.starts_with(".snapshot-"))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(snapshots(&archive), vec![".snapshot-2.json"]);
+        let manifest = fs::read_to_string(archive.join(DEFAULT_MANIFEST_NAME)).unwrap();
+        assert!(!manifest.contains("accounts"));
+
+        fs::write(
+            inbox.join("02.csv"),
+            "type,client,tx,amount\ndeposit,1,3,1.0\n",
+        )
+        .unwrap();
+        watcher.poll_once(&mut engine).unwrap();
+        assert_eq!(snapshots(&archive), vec![".snapshot-3.json"]);
+        let engine = InboxWatcher::new(&inbox, &archive)
+            .unwrap()
+            .restore_engine()
+            .unwrap();
+        assert_eq!(engine.accounts[&1].total(), Decimal::new(80, 1));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_tailer_follows_appended_rows() {
+        let dir = temp_dir("watch-tail");
+        let path = dir.join("live.csv");
+        fs::write(
+            &path,
+            "type,client,tx,amount\ndeposit,1,1,5.0\ndeposit,1,2,",
+        )
+        .unwrap();
+
+        let mut engine = Engine::new();
+        let mut tailer = FileTailer::new(&path);
+        assert_eq!(tailer.poll_once(&mut engine).unwrap(), 1);
+        assert_eq!(tailer.poll_once(&mut engine).unwrap(), 0);
+
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"1.0\nwithdrawal,1,3,2.0\n").unwrap();
+        assert_eq!(tailer.poll_once(&mut engine).unwrap(), 2);
+        assert_eq!(engine.accounts[&1].total(), Decimal::new(40, 1));
+
+        // Invalid UTF-8 is skipped, and a character split by a partial write is only
+        // decoded once the rest of its line arrives.
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"deposit,1,\xff,1.0\ndeposit,\xc3").unwrap();
+        assert_eq!(tailer.poll_once(&mut engine).unwrap(), 0);
+        file.write_all(b"\xa9,5,1.0\ndeposit,1,6,1.0\n").unwrap();
+        assert_eq!(tailer.poll_once(&mut engine).unwrap(), 2);
+        assert_eq!(engine.accounts[&1].total(), Decimal::new(50, 1));
+
+        // Truncation starts over, including the header.
+        fs::write(&path, "type,client,tx,amount\ndeposit,2,4,1.0\n").unwrap();
+        assert_eq!(tailer.poll_once(&mut engine).unwrap(), 1);
+        assert_eq!(engine.accounts[&2].total(), Decimal::new(10, 1));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
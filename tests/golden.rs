@@ -0,0 +1,117 @@
+//! Golden-file tests. Every `tests/cases/<name>.input.csv` is applied to a fresh engine
+//! and the outcome compared with two files next to it:
+//!
+//! - `<name>.report.csv`, the account report.
+//! - `<name>.rejects.txt`, one line per rejected row in input order, then one line per
+//!   malformed row.
+//!
+//! Run with `UPDATE_GOLDENS=1` to write the expected files from the current outcome
+//! instead of comparing, then review the changes before committing them.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rust_toy_tx_engine::{
+    engine::Engine,
+    transaction::{CsvTransactionSource, TransactionSource},
+};
+
+const INPUT_SUFFIX: &str = ".input.csv";
+
+/// Input files of every case, ordered by name.
+fn cases() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/cases");
+    let mut inputs: Vec<PathBuf> = fs::read_dir(&dir)
+        .unwrap_or_else(|e| panic!("Failed to read {}: {e}", dir.display()))
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.to_string_lossy().ends_with(INPUT_SUFFIX))
+        .collect();
+    inputs.sort();
+    inputs
+}
+
+/// Apply `input` and return the report and the rejects.
+fn run(input: &Path) -> (String, String) {
+    let mut engine = Engine::new();
+    let mut source = CsvTransactionSource::new(input.to_str().unwrap());
+    let mut rejects = String::new();
+    for transaction in source.transactions() {
+        let (r#type, client, tx) = (transaction.r#type, transaction.client, transaction.tx);
+        if let Err(e) = engine.apply_transaction(transaction) {
+            writeln!(
+                rejects,
+                "rejected tx {tx}, client {client}, {}: {}: {e}",
+                r#type.name(),
+                e.code()
+            )
+            .unwrap();
+        }
+    }
+    let malformed = source.malformed();
+    for error in malformed.errors() {
+        writeln!(rejects, "malformed {error}").unwrap();
+    }
+    assert_eq!(
+        malformed.count(),
+        malformed.errors().len() as u64,
+        "{}: keep cases below the limit of kept errors",
+        input.display()
+    );
+
+    let mut report = Vec::new();
+    engine.write_report(&mut report).unwrap();
+    (String::from_utf8(report).unwrap(), rejects)
+}
+
+/// Compare `actual` with the golden file at `path`, or overwrite it when updating.
+/// Returns a description of the mismatch.
+fn check(path: &Path, actual: &str, update: bool) -> Option<String> {
+    if update {
+        fs::write(path, actual)
+            .unwrap_or_else(|e| panic!("Failed to write {}: {e}", path.display()));
+        return None;
+    }
+    match fs::read_to_string(path) {
+        Ok(expected) if expected == actual => None,
+        Ok(expected) => Some(format!(
+            "{} differs\n--- expected\n{expected}--- actual\n{actual}",
+            path.display()
+        )),
+        Err(e) => Some(format!(
+            "{}: {e}, run with UPDATE_GOLDENS=1 to create it",
+            path.display()
+        )),
+    }
+}
+
+#[test]
+fn golden_cases() {
+    let update = std::env::var_os("UPDATE_GOLDENS").is_some();
+    let cases = cases();
+    assert!(!cases.is_empty(), "No cases found in tests/cases");
+
+    let mut failures = Vec::new();
+    for input in &cases {
+        let name = input.to_string_lossy();
+        let stem = name.strip_suffix(INPUT_SUFFIX).unwrap();
+        let (report, rejects) = run(input);
+        failures.extend(check(
+            Path::new(&format!("{stem}.report.csv")),
+            &report,
+            update,
+        ));
+        failures.extend(check(
+            Path::new(&format!("{stem}.rejects.txt")),
+            &rejects,
+            update,
+        ));
+    }
+    assert!(
+        failures.is_empty(),
+        "{} golden files of {} cases differ:\n\n{}",
+        failures.len(),
+        cases.len(),
+        failures.join("\n")
+    );
+}
@@ -1,6 +1,8 @@
 use rust_decimal::Decimal;
 use rust_toy_tx_engine::{
-    engine::Engine, run_engine_with_source, transaction::CsvTransactionSource,
+    engine::Engine,
+    run_engine_with_source,
+    transaction::{CsvTransactionSource, JsonLinesTransactionSource},
 };
 
 #[test]
@@ -17,11 +19,56 @@ fn test_read_sample_csv() {
     // assert account balances based on sample.csv
     let account1 = engine.accounts.get(&1).unwrap();
     assert_eq!(account1.get_available(), Decimal::new(15, 1));
-    assert_eq!(account1.held, Decimal::new(0, 2));
-    assert_eq!(account1.total, Decimal::new(15, 1));
+    assert_eq!(account1.held(), Decimal::new(0, 2));
+    assert_eq!(account1.total(), Decimal::new(15, 1));
 
     let account2 = engine.accounts.get(&2).unwrap();
     assert_eq!(account2.get_available(), Decimal::new(20, 1));
-    assert_eq!(account2.held, Decimal::new(0, 2));
-    assert_eq!(account2.total, Decimal::new(20, 1));
+    assert_eq!(account2.held(), Decimal::new(0, 2));
+    assert_eq!(account2.total(), Decimal::new(20, 1));
+}
+
+#[test]
+fn test_read_sample_jsonl_matches_csv() {
+    let mut csv_engine = Engine::new();
+    run_engine_with_source(
+        &mut csv_engine,
+        &mut CsvTransactionSource::new("tests/sample.csv"),
+    );
+
+    let mut jsonl_engine = Engine::new();
+    run_engine_with_source(
+        &mut jsonl_engine,
+        &mut JsonLinesTransactionSource::new("tests/sample.jsonl"),
+    );
+
+    assert_eq!(
+        csv_engine.account_summaries(),
+        jsonl_engine.account_summaries()
+    );
+}
+
+#[test]
+fn test_read_compressed_csv_from_reader() {
+    use flate2::{Compression, write::GzEncoder};
+    use std::io::Write;
+
+    let csv = std::fs::read("tests/sample.csv").unwrap();
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&csv).unwrap();
+    let gzipped = encoder.finish().unwrap();
+    let zstd = zstd::encode_all(csv.as_slice(), 0).unwrap();
+
+    let mut expected = Engine::new();
+    run_engine_with_source(
+        &mut expected,
+        &mut CsvTransactionSource::new("tests/sample.csv"),
+    );
+
+    for compressed in [gzipped, zstd] {
+        let mut engine = Engine::new();
+        let mut source = CsvTransactionSource::from_reader(std::io::Cursor::new(compressed));
+        run_engine_with_source(&mut engine, &mut source);
+        assert_eq!(engine.account_summaries(), expected.account_summaries());
+    }
 }
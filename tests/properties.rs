@@ -0,0 +1,224 @@
+//! Property tests of the engine against a simple reference model.
+
+use std::collections::HashMap;
+
+use proptest::prelude::*;
+use rust_decimal::Decimal;
+use rust_toy_tx_engine::{
+    engine::Engine,
+    ledger::LedgerAccount,
+    transaction::{DisputeState, Transaction, TransactionType},
+};
+
+/// ModelAccount is the reference view of one client's account.
+#[derive(Debug, Clone, Default)]
+struct ModelAccount {
+    available: Decimal,
+    held: Decimal,
+    locked: bool,
+}
+
+/// ModelTransaction is a deposit or withdrawal the model accepted.
+#[derive(Debug, Clone)]
+struct ModelTransaction {
+    client: u16,
+    amount: Decimal,
+    state: DisputeState,
+}
+
+/// Model applies the rules of the engine in the most direct way possible.
+#[derive(Debug, Default)]
+struct Model {
+    accounts: HashMap<u16, ModelAccount>,
+    transactions: HashMap<u32, ModelTransaction>,
+}
+
+impl Model {
+    /// Apply `transaction`, returning whether it was accepted.
+    fn apply(&mut self, transaction: &Transaction) -> bool {
+        let Transaction {
+            r#type,
+            client,
+            tx,
+            amount,
+        } = *transaction;
+        match r#type {
+            TransactionType::Deposit | TransactionType::Withdrawal => {
+                let Some(amount) = amount else {
+                    return false;
+                };
+                if self.transactions.contains_key(&tx) {
+                    return false;
+                }
+                let account = if r#type == TransactionType::Deposit {
+                    self.accounts.entry(client).or_default()
+                } else {
+                    match self.accounts.get_mut(&client) {
+                        Some(account) => account,
+                        None => return false,
+                    }
+                };
+                if account.locked {
+                    return false;
+                }
+                if r#type == TransactionType::Deposit {
+                    account.available += amount;
+                } else if account.available < amount {
+                    return false;
+                } else {
+                    account.available -= amount;
+                }
+                self.transactions.insert(
+                    tx,
+                    ModelTransaction {
+                        client,
+                        amount,
+                        state: DisputeState::None,
+                    },
+                );
+                true
+            }
+            TransactionType::Dispute | TransactionType::Resolve | TransactionType::Chargeback => {
+                let Some(record) = self.transactions.get_mut(&tx) else {
+                    return false;
+                };
+                let required = if r#type == TransactionType::Dispute {
+                    DisputeState::None
+                } else {
+                    DisputeState::Disputed
+                };
+                if record.client != client || record.state != required {
+                    return false;
+                }
+                let account = self.accounts.get_mut(&client).expect("Recorded client");
+                match r#type {
+                    TransactionType::Dispute => {
+                        account.available -= record.amount;
+                        account.held += record.amount;
+                        record.state = DisputeState::Disputed;
+                    }
+                    TransactionType::Resolve => {
+                        account.held -= record.amount;
+                        account.available += record.amount;
+                        record.state = DisputeState::Resolved;
+                    }
+                    _ => {
+                        account.held -= record.amount;
+                        account.locked = true;
+                        record.state = DisputeState::ChargedBack;
+                    }
+                }
+                true
+            }
+        }
+    }
+}
+
+fn transaction_type() -> impl Strategy<Value = TransactionType> {
+    prop_oneof![
+        4 => Just(TransactionType::Deposit),
+        3 => Just(TransactionType::Withdrawal),
+        2 => Just(TransactionType::Dispute),
+        1 => Just(TransactionType::Resolve),
+        1 => Just(TransactionType::Chargeback),
+    ]
+}
+
+/// Few clients and tx ids, so that disputes, duplicates and wrong clients are common.
+fn transaction() -> impl Strategy<Value = Transaction> {
+    (transaction_type(), 1..4u16, 1..24u32, 1..100_000i64).prop_map(
+        |(r#type, client, tx, amount)| Transaction {
+            r#type,
+            client,
+            tx,
+            amount: matches!(
+                r#type,
+                TransactionType::Deposit | TransactionType::Withdrawal
+            )
+            .then(|| Decimal::new(amount, 4)),
+        },
+    )
+}
+
+/// Compare the balances of every account with the model and check that they add up.
+fn check_balances(engine: &Engine, model: &Model) -> Result<(), TestCaseError> {
+    prop_assert_eq!(engine.accounts.len(), model.accounts.len());
+    for (client, expected) in &model.accounts {
+        let account = &engine.accounts[client];
+        let summary = engine
+            .account_summaries()
+            .into_iter()
+            .find(|s| s.client == *client)
+            .unwrap();
+        prop_assert_eq!(summary.available, expected.available);
+        prop_assert_eq!(summary.held, expected.held);
+        prop_assert_eq!(summary.locked, expected.locked);
+        // The total is always the sum of available and held funds.
+        prop_assert_eq!(summary.total, summary.available + summary.held);
+        prop_assert_eq!(
+            engine
+                .ledger
+                .balance(LedgerAccount::ClientAvailable(*client)),
+            account.get_available()
+        );
+        prop_assert_eq!(
+            engine.ledger.balance(LedgerAccount::ClientHeld(*client)),
+            account.held()
+        );
+
+        // Funds are only held by open disputes, and never negative.
+        let disputed: Decimal = model
+            .transactions
+            .values()
+            .filter(|t| t.client == *client && t.state == DisputeState::Disputed)
+            .map(|t| t.amount)
+            .sum();
+        prop_assert_eq!(account.held(), disputed);
+        prop_assert!(account.held() >= Decimal::ZERO);
+    }
+    prop_assert_eq!(engine.ledger.trial_balance().total(), Decimal::ZERO);
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn engine_matches_model(transactions in prop::collection::vec(transaction(), 0..200)) {
+        let mut engine = Engine::new().with_history();
+        let mut model = Model::default();
+
+        for transaction in &transactions {
+            let locked_before = engine.accounts.get(&transaction.client).is_some_and(|a| a.is_locked);
+            let total_before = engine.accounts.get(&transaction.client).map(|a| a.total());
+
+            let accepted = model.apply(transaction);
+            let result = engine.apply_transaction(transaction.clone());
+            prop_assert_eq!(result.is_ok(), accepted, "{:?}: {:?}", transaction, result);
+
+            // Locked accounts never gain funds.
+            if locked_before {
+                let account = &engine.accounts[&transaction.client];
+                prop_assert!(account.is_locked);
+                prop_assert!(Some(account.total()) <= total_before);
+                if transaction.r#type == TransactionType::Deposit {
+                    prop_assert!(result.is_err());
+                }
+            }
+            check_balances(&engine, &model)?;
+        }
+
+
+        // Every tx id is applied as a deposit or withdrawal at most once.
+        let history = engine.history.as_ref().unwrap();
+        let mut applied: HashMap<u32, usize> = HashMap::new();
+        for transaction in history.iter().filter(|t| {
+            matches!(t.r#type, TransactionType::Deposit | TransactionType::Withdrawal)
+        }) {
+            *applied.entry(transaction.tx).or_default() += 1;
+        }
+        prop_assert!(applied.values().all(|count| *count == 1));
+        prop_assert_eq!(applied.len(), model.transactions.len());
+        for (tx, record) in &engine.transactions {
+            prop_assert_eq!(record.dispute_state, model.transactions[tx].state);
+        }
+    }
+}